@@ -1,6 +1,12 @@
+use std::cell::Cell;
 use std::fmt::Write;
 use std::io::BufReader;
-use std::ops::Deref;
+use std::mem;
+use std::ops::{self, Deref};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 use crate::job::Job;
 
@@ -12,9 +18,12 @@ use helix_core::{line_ending, shellwords::Shellwords};
 use helix_stdx::path::home_dir;
 use helix_view::document::{read_to_string, DEFAULT_LANGUAGE_NAME};
 use helix_view::editor::{CloseError, ConfigEvent};
+use regex::Regex;
 use serde_json::Value;
 use shellwords::{Args, Flag, ParseMode};
-use ui::completers::{self, Completer};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader};
+use tokio::sync::Notify;
+use ui::completers::{self, Completer, Completion};
 
 #[derive(Clone)]
 pub struct TypableCommand {
@@ -27,7 +36,7 @@ pub struct TypableCommand {
 }
 
 impl TypableCommand {
-    fn completer_for_argument_number(&self, n: usize) -> &Completer {
+    fn completer_for_argument_number(&self, n: usize) -> &ArgCompleter {
         self.signature
             .completer
             .positional_args
@@ -35,26 +44,70 @@ impl TypableCommand {
             .unwrap_or(&self.signature.completer.var_args)
     }
 
+    /// Names of the expected positional arguments, parsed from the command's `accepts`
+    /// usage string (e.g. `"<option> <value>"` -> `["option", "value"]`). Commands
+    /// without a named `accepts` string (or whose `accepts` doesn't cover a given
+    /// positional) fall back to a plain "argument N" in error messages.
+    fn positional_names(&self) -> Vec<&'static str> {
+        self.signature
+            .accepts
+            .map(|accepts| {
+                accepts
+                    .split_whitespace()
+                    .map(|token| token.trim_start_matches('<').trim_end_matches('>'))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Describes the positional argument at `index` (0-based) for arity error messages,
+    /// e.g. `<path> as argument 1`, falling back to `argument 1` when unnamed.
+    fn describe_positional(&self, index: usize) -> String {
+        match self.positional_names().get(index) {
+            Some(name) => format!("<{name}> as argument {}", index + 1),
+            None => format!("argument {}", index + 1),
+        }
+    }
+
     pub fn ensure_signature(&self, count: usize) -> anyhow::Result<()> {
         match self.signature.positionals {
-            (0, Some(0)) => ensure!(count == 0, "`:{}` doesn't take any arguments", self.name),
-            (min, Some(max)) if min == max => ensure!(
-                (min..=max).contains(&count),
-                "`:{}` needs `{min}` argument{}, got {count}",
-                self.name,
-                if min > 1 { "'s" } else { "" }
-            ),
-            (min, Some(max)) if min == max => ensure!(
-                (min..=max).contains(&count),
-                // TODO: better wording for more cases
-                "`:{}` needs at least `{min}` arguments and at most `{max}`, got {count}",
+            (0, Some(0)) => ensure!(
+                count == 0,
+                "`:{}` doesn't take any arguments, got {count}",
                 self.name
             ),
-            (min, _) => ensure!(
-                (min..).contains(&count),
-                "`:{}` needs at least `{min}` argument{}",
+            (min, Some(max)) if min == max => {
+                ensure!(
+                    count >= min,
+                    "`:{}` needs {}, got {count}",
+                    self.name,
+                    self.describe_positional(count)
+                );
+                ensure!(
+                    count <= max,
+                    "`:{}` takes exactly {min} argument{}, got {count}",
+                    self.name,
+                    if min == 1 { "" } else { "s" }
+                );
+            }
+            (min, Some(max)) => {
+                ensure!(
+                    count >= min,
+                    "`:{}` needs {}, got {count}",
+                    self.name,
+                    self.describe_positional(count)
+                );
+                ensure!(
+                    count <= max,
+                    "`:{}` takes at most {max} arguments (accepts [{min}, {max}]), got {count}",
+                    self.name
+                );
+            }
+            (min, None) => ensure!(
+                count >= min,
+                "`:{}` needs {}, got {count}",
                 self.name,
-                if min > 1 { "s" } else { "" }
+                self.describe_positional(count)
             ),
         }
 
@@ -207,35 +260,143 @@ pub struct CommandSignature {
 #[derive(Clone)]
 pub struct CommandCompleter {
     // Arguments with specific completion methods based on their position.
-    positional_args: &'static [Completer],
+    positional_args: &'static [ArgCompleter],
 
     // All remaining arguments will use this completion method, if set.
-    var_args: Completer,
+    var_args: ArgCompleter,
 }
 
 impl CommandCompleter {
     const fn none() -> Self {
         Self {
             positional_args: &[],
-            var_args: completers::none,
+            var_args: ArgCompleter::Function(completers::none),
         }
     }
 
-    const fn positional(completers: &'static [Completer]) -> Self {
+    const fn positional(completers: &'static [ArgCompleter]) -> Self {
         Self {
             positional_args: completers,
-            var_args: completers::none,
+            var_args: ArgCompleter::Function(completers::none),
         }
     }
 
     const fn all(completer: Completer) -> Self {
         Self {
             positional_args: &[],
-            var_args: completer,
+            var_args: ArgCompleter::Function(completer),
+        }
+    }
+
+    /// Fuzzy-matches the argument against a fixed list of accepted values, e.g. the
+    /// enum-style arguments taken by `:set-line-ending` or `:indent-style`.
+    const fn values(values: &'static [&'static str]) -> Self {
+        Self {
+            positional_args: &[],
+            var_args: ArgCompleter::Values(values),
+        }
+    }
+
+    /// Completes argument one as an executable on `$PATH`, and every argument after that
+    /// as a filename. Used by the `run-shell-command`/`pipe`/`pipe-to`/`append-output`/
+    /// `insert-output` family, where the first word names the program to run.
+    const fn executable() -> Self {
+        Self {
+            positional_args: &[ArgCompleter::Function(complete_executable)],
+            var_args: ArgCompleter::Function(completers::filename),
+        }
+    }
+}
+
+/// Lists the names of every regular file or symlink found in a `$PATH` directory, for
+/// completing argument one of the `:run-shell-command` family. Execute permissions are not
+/// checked.
+fn complete_executable(editor: &Editor, input: &str) -> Vec<(ops::Range<usize>, Completion)> {
+    let _ = editor;
+
+    let candidates: Vec<String> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .metadata()
+                .map(|meta| meta.is_file() || meta.file_type().is_symlink())
+                .unwrap_or(false)
+        })
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+
+    fuzzy_match(input, candidates.iter().map(String::as_str), false)
+        .into_iter()
+        .map(|(name, _score)| (0..input.len(), name.to_string().into()))
+        .collect()
+}
+
+/// A single argument's completion method: either delegate to a [`Completer`] function
+/// pointer, fuzzy-match against a static list of candidate values, or (for `set-option`
+/// and friends) look at the value already typed in argument one to decide what the next
+/// argument should offer.
+#[derive(Clone, Copy)]
+pub enum ArgCompleter {
+    Function(Completer),
+    Values(&'static [&'static str]),
+    ConfigValue,
+}
+
+impl ArgCompleter {
+    fn complete(
+        &self,
+        editor: &Editor,
+        args: &Args,
+        input: &str,
+    ) -> Vec<(ops::Range<usize>, Completion)> {
+        match self {
+            ArgCompleter::Function(completer) => completer(editor, input),
+            ArgCompleter::Values(values) => fuzzy_match(input, values.iter().copied(), false)
+                .into_iter()
+                .map(|(value, _score)| (0..input.len(), value.into()))
+                .collect(),
+            ArgCompleter::ConfigValue => complete_option_value(editor, args, input),
         }
     }
 }
 
+/// Completes the value positional of `:set-option`/`:toggle-option` from the JSON type of
+/// the option named in argument one: booleans offer `true`/`false`, and everything else is
+/// prefilled with the option's current value so the user can edit it in place rather than
+/// retype it from scratch.
+fn complete_option_value(
+    editor: &Editor,
+    args: &Args,
+    input: &str,
+) -> Vec<(ops::Range<usize>, Completion)> {
+    let Some(key) = args.first() else {
+        return Vec::new();
+    };
+
+    let config = serde_json::json!(editor.config().deref());
+    let pointer = format!("/{}", key.to_lowercase().replace('.', "/"));
+    let Some(value) = config.pointer(&pointer) else {
+        return Vec::new();
+    };
+
+    let candidates: Vec<String> = match value {
+        Value::Bool(_) => vec!["true".to_string(), "false".to_string()],
+        Value::Null => Vec::new(),
+        Value::String(current) => vec![current.clone()],
+        other => vec![other.to_string()],
+    };
+
+    fuzzy_match(input, candidates.iter().map(String::as_str), false)
+        .into_iter()
+        .map(|(value, _score)| (0..input.len(), value.to_string().into()))
+        .collect()
+}
+
 fn quit(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
     log::debug!("quitting...");
 
@@ -294,7 +455,8 @@ fn open(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow:
             cx.jobs.callback(callback);
         } else {
             // Otherwise, just open the file
-            let _ = cx.editor.open(&path, Action::Replace)?;
+            let doc_id = cx.editor.open(&path, Action::Replace)?;
+            record_disk_stamp(doc_id, &path);
             let (view, doc) = current!(cx.editor);
             let pos = Selection::point(pos_at_coords(doc.text().slice(..), pos, true));
             doc.set_selection(view.id, pos);
@@ -495,13 +657,75 @@ fn buffer_previous(
     Ok(())
 }
 
+/// A cheap fingerprint of a file's on-disk state, used to detect edits made by another
+/// process since Helix last read or wrote the file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct DiskStamp {
+    mtime: Option<SystemTime>,
+    len: u64,
+}
+
+impl DiskStamp {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            mtime: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+static DISK_STAMPS: Lazy<Mutex<HashMap<DocumentId, DiskStamp>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Records the current on-disk state of `path` for `doc_id` so a later `write_impl` call
+/// can detect whether the file changed out from under us in the meantime.
+fn record_disk_stamp(doc_id: DocumentId, path: &Path) {
+    if let Some(stamp) = DiskStamp::read(path) {
+        DISK_STAMPS.lock().unwrap().insert(doc_id, stamp);
+    } else {
+        DISK_STAMPS.lock().unwrap().remove(&doc_id);
+    }
+}
+
+/// Bails with an error if `path` changed on disk since the last time it was read or saved
+/// by this document, unless `force` is set. This mirrors the "file has changed on disk"
+/// protection found in other editors, preventing a bare `:w` from silently clobbering
+/// edits made by another process or tool.
+fn ensure_disk_unchanged(doc_id: DocumentId, path: &Path, force: bool) -> anyhow::Result<()> {
+    if force {
+        return Ok(());
+    }
+
+    let Some(&known) = DISK_STAMPS.lock().unwrap().get(&doc_id) else {
+        return Ok(());
+    };
+
+    match DiskStamp::read(path) {
+        Some(current) if current != known => bail!(
+            "'{}' has changed on disk since it was last read. Use `:w!` to overwrite anyway.",
+            path.display()
+        ),
+        _ => Ok(()),
+    }
+}
+
 fn write_impl(
     cx: &mut compositor::Context,
     path: Option<&Cow<'_, str>>,
     force: bool,
     format: bool,
+    no_atomic: bool,
+    backup: bool,
 ) -> anyhow::Result<()> {
     let config = cx.editor.config();
+    // `backup` and `atomic_save` are new `[editor]` keys on `helix_view::editor::Config`
+    // (helix-view/src/editor.rs, outside this crate), both defaulting to `false` so existing
+    // `config.toml`s keep writing exactly as before unless a user opts in.
+    let backup = backup || config.backup;
+    // `--backup` only has an effect on the staged/atomic write path, so requesting it
+    // implies atomic saving even when `atomic_save` isn't set in the config.
+    let atomic = !no_atomic && (config.atomic_save || backup);
     let jobs = &mut cx.jobs;
     let (view, doc) = current!(cx.editor);
 
@@ -514,6 +738,10 @@ fn write_impl(
 
     let path: Option<PathBuf> = path.map(|path| path.as_ref().into());
 
+    if let Some(target) = path.as_deref().or_else(|| doc.path().map(PathBuf::as_path)) {
+        ensure_disk_unchanged(doc.id(), target, force)?;
+    }
+
     let fmt = if config.auto_format && format {
         doc.auto_format().map(|fmt| {
             let callback = make_format_callback(
@@ -532,9 +760,152 @@ fn write_impl(
 
     if fmt.is_none() {
         let id = doc.id();
-        cx.editor.save(id, path, force)?;
+        if atomic {
+            let target = path
+                .clone()
+                .or_else(|| doc.path().cloned())
+                .context("cannot write a buffer without a filename")?;
+            save_atomic(doc, &target, backup, force)?;
+            doc.reset_modified();
+            doc.set_path(Some(&target));
+            notify_saved(doc, &target);
+            record_disk_stamp(id, &target);
+        } else {
+            let target = path.clone().or_else(|| doc.path().cloned());
+            cx.editor.save(id, path, force)?;
+            if let Some(target) = target {
+                record_disk_stamp(id, &target);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Notifies any language servers attached to `doc` that `path` was just written to disk.
+///
+/// `cx.editor.save` sends this `textDocument/didSave` notification itself as part of its
+/// write; since the atomic write path replaces `path` on disk without going through
+/// `Editor::save`, it has to send the notification itself so servers stay in sync.
+fn notify_saved(doc: &Document, path: &Path) {
+    let Ok(uri) = helix_lsp::Url::from_file_path(path) else {
+        return;
+    };
+    let identifier = helix_lsp::lsp::TextDocumentIdentifier::new(uri);
+    for ls in doc.language_servers() {
+        ls.text_document_did_save(identifier.clone(), doc.text());
+    }
+}
+
+/// Writes `doc`'s contents into a fresh temporary file beside `path`, fsyncing it, without
+/// touching `path` itself. Returns the temporary file's location so it can later be
+/// committed with [`commit_staged`] or abandoned by simply removing it, which lets callers
+/// stage several documents before deciding whether to keep any of them. When `force` is
+/// set, missing parent directories of `path` are created, matching `force_write`'s
+/// "creating necessary subdirectories" behavior on the non-atomic path. The document's own
+/// encoding and BOM are honored, the same as a normal (non-atomic) save.
+fn stage_atomic(doc: &Document, path: &Path, force: bool) -> anyhow::Result<PathBuf> {
+    use std::io::Write as _;
+
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .context("cannot write to a path without a file name")?;
+
+    if force && !dir.as_os_str().is_empty() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create directory {dir:?}"))?;
+    }
+
+    let tmp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let write_result = (|| -> anyhow::Result<()> {
+        let mut tmp_file = std::fs::File::create(&tmp_path)
+            .with_context(|| format!("failed to create temporary file {tmp_path:?}"))?;
+
+        let (encoding, has_bom) = doc.encoding_with_bom_info();
+        if has_bom {
+            let bom: &[u8] = if encoding == encoding::UTF_16LE {
+                &[0xFF, 0xFE]
+            } else if encoding == encoding::UTF_16BE {
+                &[0xFE, 0xFF]
+            } else {
+                &[0xEF, 0xBB, 0xBF]
+            };
+            tmp_file.write_all(bom)?;
+        }
+
+        let mut encoder = encoding.new_encoder();
+        let mut buf = Vec::new();
+        let mut chunks = doc.text().slice(..).chunks().peekable();
+        while let Some(chunk) = chunks.next() {
+            let is_last = chunks.peek().is_none();
+            buf.clear();
+            let (result, _input_bytes_read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(chunk, &mut buf, is_last);
+            if let encoding::EncoderResult::Unmappable(char) = result {
+                bail!("{char:?} cannot be mapped to {}", encoding.name());
+            }
+            tmp_file.write_all(&buf)?;
+        }
+
+        tmp_file.sync_all()?;
+        Ok(())
+    })();
+
+    if let Err(err) = write_result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+
+    Ok(tmp_path)
+}
+
+/// Commits a file staged by [`stage_atomic`]: backs up the previous contents of `path` if
+/// `backup` is set, then atomically renames the staged file over it.
+fn commit_staged(tmp_path: &Path, path: &Path, backup: bool) -> anyhow::Result<()> {
+    if backup {
+        if let Err(err) = backup_existing_file(path) {
+            let _ = std::fs::remove_file(tmp_path);
+            return Err(err);
+        }
     }
 
+    std::fs::rename(tmp_path, path)
+        .with_context(|| format!("failed to atomically replace {path:?}"))
+}
+
+/// Writes the document's contents to `path` by staging a temporary file in the same
+/// directory and atomically renaming it over the destination so a crash mid-write can
+/// never leave a truncated or corrupted file behind. When `backup` is set, the previous
+/// contents of `path` (if any) are preserved at `path` with a trailing `~` before being
+/// replaced. When `force` is set, missing parent directories of `path` are created.
+fn save_atomic(doc: &Document, path: &Path, backup: bool, force: bool) -> anyhow::Result<()> {
+    let tmp_path = stage_atomic(doc, path, force)?;
+    commit_staged(&tmp_path, path, backup)
+}
+
+/// Copies the existing contents of `path` to a sibling `path~` backup file before it gets
+/// overwritten. A no-op if `path` doesn't exist yet (e.g. the first save of a new file).
+fn backup_existing_file(path: &Path) -> anyhow::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let mut backup_name = path.as_os_str().to_owned();
+    backup_name.push("~");
+    let backup_path = PathBuf::from(backup_name);
+
+    std::fs::copy(path, &backup_path)
+        .with_context(|| format!("failed to create backup {backup_path:?}"))?;
+
     Ok(())
 }
 
@@ -552,7 +923,14 @@ fn write(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow
         return Ok(());
     }
 
-    write_impl(cx, args.first(), false, args.has_flag("no-format"))
+    write_impl(
+        cx,
+        args.first(),
+        false,
+        args.has_flag("no-format"),
+        args.has_flag("no-atomic"),
+        args.has_flag("backup"),
+    )
 }
 
 fn force_write(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
@@ -560,7 +938,14 @@ fn force_write(cx: &mut compositor::Context, args: Args, event: PromptEvent) ->
         return Ok(());
     }
 
-    write_impl(cx, args.first(), true, args.has_flag("no-format"))
+    write_impl(
+        cx,
+        args.first(),
+        true,
+        args.has_flag("no-format"),
+        args.has_flag("no-atomic"),
+        args.has_flag("backup"),
+    )
 }
 
 fn write_buffer_close(
@@ -572,7 +957,7 @@ fn write_buffer_close(
         return Ok(());
     }
 
-    write_impl(cx, args.first(), false, args.has_flag("no-format"))?;
+    write_impl(cx, args.first(), false, args.has_flag("no-format"), false, false)?;
 
     let document_ids = buffer_gather_paths_impl(cx.editor, args);
     buffer_close_by_ids_impl(cx, &document_ids, false)
@@ -587,7 +972,7 @@ fn force_write_buffer_close(
         return Ok(());
     }
 
-    write_impl(cx, args.first(), true, args.has_flag("no-format"))?;
+    write_impl(cx, args.first(), true, args.has_flag("no-format"), false, false)?;
 
     let document_ids = buffer_gather_paths_impl(cx.editor, args);
     buffer_close_by_ids_impl(cx, &document_ids, false)
@@ -618,6 +1003,8 @@ fn format(cx: &mut compositor::Context, _args: Args, event: PromptEvent) -> anyh
     Ok(())
 }
 
+const INDENT_STYLE_VALUES: &[&str] = &["tabs", "1", "2", "3", "4", "5", "6", "7", "8"];
+
 fn set_indent_style(
     cx: &mut compositor::Context,
     args: Args,
@@ -659,6 +1046,11 @@ fn set_indent_style(
     Ok(())
 }
 
+#[cfg(not(feature = "unicode-lines"))]
+const LINE_ENDING_VALUES: &[&str] = &["crlf", "lf"];
+#[cfg(feature = "unicode-lines")]
+const LINE_ENDING_VALUES: &[&str] = &["crlf", "lf", "cr", "ff", "nel"];
+
 /// Sets or reports the current document's line ending setting.
 fn set_line_ending(
     cx: &mut compositor::Context,
@@ -769,7 +1161,7 @@ fn write_quit(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> a
         return Ok(());
     }
 
-    write_impl(cx, args.first(), false, args.has_flag("no-format"))?;
+    write_impl(cx, args.first(), false, args.has_flag("no-format"), false, false)?;
     cx.block_try_flush_writes()?;
     quit(cx, Args::empty(), event)
 }
@@ -783,7 +1175,7 @@ fn force_write_quit(
         return Ok(());
     }
 
-    write_impl(cx, args.first(), true, args.has_flag("no-format"))?;
+    write_impl(cx, args.first(), true, args.has_flag("no-format"), false, false)?;
     cx.block_try_flush_writes()?;
     force_quit(cx, Args::empty(), event)
 }
@@ -821,6 +1213,24 @@ pub(super) fn buffers_remaining_impl(editor: &mut Editor) -> anyhow::Result<()>
     Ok(())
 }
 
+/// Restores every buffer recorded in `committed` to its pre-write contents, undoing a
+/// partially completed `:write-all` after a later buffer in the batch failed to save. The
+/// pre-write contents are kept in memory rather than as a sibling file on disk, so a
+/// successful `:write-all` never leaves transient backup files for file-watchers or `git
+/// status` to notice.
+fn roll_back_committed(committed: &[(DocumentId, PathBuf, Option<Vec<u8>>)]) {
+    for (_, path, previous_contents) in committed.iter().rev() {
+        match previous_contents {
+            Some(previous_contents) => {
+                let _ = std::fs::write(path, previous_contents);
+            }
+            None => {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
 pub fn write_all_impl(
     cx: &mut compositor::Context,
     force: bool,
@@ -854,6 +1264,13 @@ pub fn write_all_impl(
         })
         .collect();
 
+    // Stage every buffer that doesn't need asynchronous formatting into a temporary file
+    // next to its target before committing any of them. If staging any one of them fails,
+    // none of the targets on disk have been touched yet, so the batch can simply be
+    // abandoned; if a later commit fails, the buffers already committed are rolled back.
+    let mut staged: Vec<(DocumentId, PathBuf, PathBuf)> = Vec::new();
+    let mut stage_error = None;
+
     for (doc_id, target_view) in saves {
         let doc = doc_mut!(cx.editor, &doc_id);
         let view = view_mut!(cx.editor, target_view);
@@ -880,9 +1297,64 @@ pub fn write_all_impl(
             None
         };
 
-        if fmt.is_none() {
-            cx.editor.save::<PathBuf>(doc_id, None, force)?;
+        if fmt.is_some() {
+            // Formatting happens asynchronously and saves itself once it completes, so it
+            // falls outside this transaction.
+            continue;
+        }
+
+        let path = doc.path().cloned().expect("checked above: buffer has a path");
+        match stage_atomic(doc, &path, force) {
+            Ok(tmp_path) => staged.push((doc_id, path, tmp_path)),
+            Err(err) => {
+                stage_error = Some(err.context(format!("failed to stage '{}'", path.display())));
+                break;
+            }
+        }
+    }
+
+    if let Some(err) = stage_error {
+        for (_, _, tmp_path) in &staged {
+            let _ = std::fs::remove_file(tmp_path);
+        }
+        return Err(err.context("write-all aborted: no buffers were written"));
+    }
+
+    let mut committed: Vec<(DocumentId, PathBuf, Option<Vec<u8>>)> = Vec::new();
+    for (doc_id, path, tmp_path) in staged {
+        // Keep the previous contents in memory (independent of the user-facing `~` backup
+        // below) so a later failure in this batch can restore it, without leaving a sibling
+        // backup file on disk for the common case where the whole batch succeeds. These are
+        // the raw bytes read back from disk, not `doc.text()`, so restoring them is exact
+        // regardless of the document's encoding; `stage_atomic` is what has to encode through
+        // `doc.encoding_with_bom_info()` when writing the replacement out.
+        let previous_contents = if path.exists() {
+            match std::fs::read(&path) {
+                Ok(contents) => Some(contents),
+                Err(err) => {
+                    let _ = std::fs::remove_file(&tmp_path);
+                    roll_back_committed(&committed);
+                    return Err(err).context(format!(
+                        "write-all aborted: could not read the previous contents of '{}'; buffers already saved in this batch were restored",
+                        path.display()
+                    ));
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Err(err) = commit_staged(&tmp_path, &path, config.backup) {
+            roll_back_committed(&committed);
+            return Err(err.context(
+                "write-all aborted: buffers already saved in this batch were restored",
+            ));
         }
+
+        notify_saved(doc!(cx.editor, &doc_id), &path);
+        record_disk_stamp(doc_id, &path);
+        doc_mut!(cx.editor, &doc_id).reset_modified();
+        committed.push((doc_id, path, previous_contents));
     }
 
     if !errors.is_empty() && !force {
@@ -998,6 +1470,18 @@ fn force_cquit(cx: &mut compositor::Context, args: Args, event: PromptEvent) ->
 }
 
 fn theme(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
+    if args.has_flag("auto") {
+        if event != PromptEvent::Validate {
+            return Ok(());
+        }
+        ensure!(
+            args.len() == 2,
+            ":theme --auto takes exactly two arguments: <light-theme> <dark-theme>"
+        );
+        return theme_auto_impl(cx, &args[0], &args[1]);
+    }
+    ensure!(args.len() <= 1, ":theme takes a single <theme>, or two with --auto");
+
     let true_color = cx.editor.config.load().true_color || crate::true_color();
     match event {
         PromptEvent::Abort => {
@@ -1038,6 +1522,122 @@ fn theme(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow
     Ok(())
 }
 
+/// Parses an OSC 11 reply (`ESC ] 11 ; rgb:RRRR/GGGG/BBBB BEL`, as answered by most modern
+/// terminal emulators in response to the `ESC ] 11 ; ? BEL` query) into light/dark by
+/// relative luminance. Exposed separately from the query itself, which has to go through the
+/// application's own terminal-input reader (see [`detect_dark_appearance`]) rather than
+/// reading stdin directly here.
+fn classify_osc11_background(reply: &str) -> Option<bool> {
+    let rgb = reply.split("rgb:").nth(1)?;
+    let mut channels = rgb.split(['/', '\x07', '\x1b']).filter(|c| !c.is_empty()).take(3).map(
+        |channel| {
+            let hex = &channel[..channel.len().min(4)];
+            u32::from_str_radix(hex, 16).unwrap_or(0) as f64 / (16f64.powi(hex.len() as i32) - 1.0)
+        },
+    );
+    let (r, g, b) = (channels.next()?, channels.next()?, channels.next()?);
+
+    // Relative luminance using the ITU-R BT.709 coefficients; below the midpoint reads dark.
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    Some(luminance < 0.5)
+}
+
+/// Detects whether the terminal or OS is currently using a light or dark appearance, so
+/// [`theme_auto`] can pick a matching theme.
+///
+/// An earlier version of this function queried the terminal's background color directly via
+/// OSC 11, reading the reply off a background thread racing `std::io::stdin()` against the
+/// application's own crossterm event reader -- which owns the terminal's raw-mode input and
+/// can just as easily read (and discard) the OSC reply as a key event, or have a real
+/// keystroke stolen by our thread instead. There's no way to do that read safely from a
+/// typable command in isolation; it requires the OSC query and its reply to go through the
+/// same input stream crossterm already owns, i.e. a hook in the application's event loop
+/// (`helix-term/src/application.rs`) that recognizes the reply and forwards it here, which is
+/// out of reach from this file. [`classify_osc11_background`] is kept so that hook has
+/// somewhere to parse the reply once it exists, but this function no longer issues the query
+/// itself. Until then, we fall back to `$COLORFGBG`, which only reflects what the *shell* was
+/// told at startup, then to asking the OS directly: macOS and most Linux desktops track a
+/// system-wide appearance setting. Returns `None` when no signal is available.
+///
+/// This only samples the appearance once, at the moment `:theme-auto` (or `:theme --auto`)
+/// runs — there's no live re-evaluation on a terminal resize or focus change, since reacting
+/// to those needs a hook into the application's event loop rather than anything reachable
+/// from a typable command. If that's needed, the command should be re-invoked (e.g. bound to
+/// a key, or from a shell script watching for appearance changes) rather than relying on it
+/// to happen automatically.
+fn detect_dark_appearance() -> Option<bool> {
+    if let Ok(colorfgbg) = std::env::var("COLORFGBG") {
+        if let Some(bg) = colorfgbg.rsplit(';').next().and_then(|bg| bg.parse::<u8>().ok()) {
+            return Some(bg < 8);
+        }
+    }
+
+    if cfg!(target_os = "macos") {
+        let output = std::process::Command::new("defaults")
+            .args(["read", "-g", "AppleInterfaceStyle"])
+            .output()
+            .ok()?;
+        return Some(output.status.success());
+    }
+
+    if cfg!(target_os = "linux") {
+        let output = std::process::Command::new("gsettings")
+            .args(["get", "org.gnome.desktop.interface", "color-scheme"])
+            .output()
+            .ok()?;
+        if output.status.success() {
+            let scheme = String::from_utf8_lossy(&output.stdout).to_lowercase();
+            return Some(scheme.contains("dark"));
+        }
+    }
+
+    None
+}
+
+/// Switches to `light-theme` or `dark-theme` depending on the OS or terminal's current
+/// appearance, falling back to `dark-theme` when the appearance can't be determined. Backs
+/// both the standalone `:theme-auto <light> <dark>` command and `:theme --auto <light>
+/// <dark>`.
+///
+/// There's no `[editor]` config block to set `light-theme`/`dark-theme` once and have them
+/// picked up automatically on startup — that would need `helix_view::editor::Config` (outside
+/// this crate) to grow matching fields and the startup path to call this, neither of which
+/// lives in this file. Until then, switching has to be invoked explicitly, e.g. bound to a key
+/// or run from `init.scm`/a shell alias.
+fn theme_auto(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
+    if event != PromptEvent::Validate {
+        return Ok(());
+    }
+    theme_auto_impl(cx, &args[0], &args[1])
+}
+
+fn theme_auto_impl(
+    cx: &mut compositor::Context,
+    light_theme: &str,
+    dark_theme: &str,
+) -> anyhow::Result<()> {
+    let is_dark = detect_dark_appearance().unwrap_or(true);
+    let theme_name = if is_dark { dark_theme } else { light_theme };
+
+    let true_color = cx.editor.config.load().true_color || crate::true_color();
+    let theme = cx
+        .editor
+        .theme_loader
+        .load(theme_name)
+        .map_err(|err| anyhow::anyhow!("Could not load theme: {}", err))?;
+    if !(true_color || theme.is_16_color()) {
+        bail!("Unsupported theme: theme requires true color support");
+    }
+
+    cx.editor.set_theme(theme);
+    cx.editor.set_status(format!(
+        "Switched to '{theme_name}' ({})",
+        if is_dark { "dark" } else { "light" }
+    ));
+
+    Ok(())
+}
+
 fn yank_main_selection_to_clipboard(
     cx: &mut compositor::Context,
     _args: Args,
@@ -1335,6 +1935,19 @@ fn get_character_info(
         String::new()
     };
 
+    // Annotate each codepoint with its Unicode name and General Category abbreviation
+    let names = if encoding == encoding::UTF_8 {
+        let names = grapheme
+            .chars()
+            .map(unicode_name_and_category)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(" [{names}]")
+    } else {
+        String::new()
+    };
+
     let hex = {
         let mut encoder = encoding.new_encoder();
         let max_encoded_len = encoder
@@ -1370,11 +1983,47 @@ fn get_character_info(
     };
 
     cx.editor
-        .set_status(format!("\"{printable}\"{unicode}{dec} Hex{hex}"));
+        .set_status(format!("\"{printable}\"{unicode}{dec} Hex{hex}{names}"));
 
     Ok(())
 }
 
+/// Returns the Unicode name (e.g. "LATIN SMALL LETTER A") and a short General Category
+/// abbreviation (e.g. "Ll") for `c`, for display in `:character-info`. Codepoints with
+/// no assigned name (unassigned or private-use) are reported as "unnamed".
+fn unicode_name_and_category(c: char) -> String {
+    let name = unicode_names2::name(c)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| "unnamed".to_string());
+
+    format!("{name} ({})", general_category_abbreviation(c))
+}
+
+/// A coarse General Category abbreviation for `c`, telling letters, digits, ASCII
+/// punctuation, whitespace and control characters apart at a glance. Only ASCII punctuation
+/// is recognised as `Po`; any other character none of these tests match (non-ASCII
+/// punctuation, symbols, combining marks, format characters such as U+200D) is reported as
+/// `Cn`.
+fn general_category_abbreviation(c: char) -> &'static str {
+    if c.is_control() {
+        "Cc"
+    } else if c.is_whitespace() {
+        "Zs"
+    } else if c.is_uppercase() {
+        "Lu"
+    } else if c.is_lowercase() {
+        "Ll"
+    } else if c.is_alphabetic() {
+        "Lo"
+    } else if c.is_numeric() {
+        "Nd"
+    } else if c.is_ascii_punctuation() {
+        "Po"
+    } else {
+        "Cn"
+    }
+}
+
 /// Reload the [`Document`] from its source file.
 fn reload(cx: &mut compositor::Context, _args: Args, event: PromptEvent) -> anyhow::Result<()> {
     if event != PromptEvent::Validate {
@@ -1387,6 +2036,7 @@ fn reload(cx: &mut compositor::Context, _args: Args, event: PromptEvent) -> anyh
         view.ensure_cursor_in_view(doc, scrolloff);
     })?;
     if let Some(path) = doc.path() {
+        record_disk_stamp(doc.id(), path);
         cx.editor
             .language_servers
             .file_event_handler
@@ -1433,6 +2083,7 @@ fn reload_all(cx: &mut compositor::Context, _args: Args, event: PromptEvent) ->
         }
 
         if let Some(path) = doc.path() {
+            record_disk_stamp(doc_id, path);
             cx.editor
                 .language_servers
                 .file_event_handler
@@ -1464,6 +2115,23 @@ fn update(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyho
     }
 }
 
+/// Parses the words following the command name in `:lsp-workspace-command` into the JSON
+/// `arguments` array expected by `workspace/executeCommand`. Each word is parsed as JSON
+/// when possible (so `42` or `{"foo":1}` come through as their native type) and falls back
+/// to a plain JSON string otherwise.
+fn lsp_workspace_command_arguments(words: &[Cow<'_, str>]) -> Option<Vec<Value>> {
+    if words.is_empty() {
+        return None;
+    }
+
+    Some(
+        words
+            .iter()
+            .map(|word| serde_json::from_str(word).unwrap_or_else(|_| Value::String(word.to_string())))
+            .collect(),
+    )
+}
+
 fn lsp_workspace_command(
     cx: &mut compositor::Context,
     args: Args,
@@ -1512,7 +2180,26 @@ fn lsp_workspace_command(
                         commands,
                         (),
                         move |cx, (ls_id, command), _action| {
-                            execute_lsp_command(cx.editor, *ls_id, command.clone());
+                            let ls_id = *ls_id;
+                            let command = command.clone();
+                            let prompt = ui::Prompt::new(
+                                "arguments:".into(),
+                                None,
+                                ui::completers::none,
+                                move |cx, input, event| {
+                                    if event != PromptEvent::Validate {
+                                        return;
+                                    }
+                                    let words: Vec<Cow<str>> = input
+                                        .split_whitespace()
+                                        .map(Cow::Borrowed)
+                                        .collect();
+                                    let mut command = command.clone();
+                                    command.arguments = lsp_workspace_command_arguments(&words);
+                                    execute_lsp_command(cx.editor, ls_id, command);
+                                },
+                            );
+                            cx.push_layer(Box::new(prompt));
                         },
                     );
                     compositor.push(Box::new(overlaid(picker)))
@@ -1523,6 +2210,7 @@ fn lsp_workspace_command(
         cx.jobs.callback(callback);
     } else {
         let command = args[0].to_string();
+        let arguments = lsp_workspace_command_arguments(&args[1..]);
 
         let matches: Vec<_> = ls_id_commands
             .filter(|(_ls_id, c)| *c == &command)
@@ -1535,7 +2223,7 @@ fn lsp_workspace_command(
                     *ls_id,
                     helix_lsp::lsp::Command {
                         title: command.clone(),
-                        arguments: None,
+                        arguments,
                         command,
                     },
                 );
@@ -1957,9 +2645,8 @@ fn set_option(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> a
             .map_err(|err| anyhow::anyhow!("Could not parse field `{field}`: {err}"))?
     };
 
-    let config = serde_json::from_value(config).expect(
-        "`Config` was already deserialized, serialization is just a 'repacking' and should be valid",
-    );
+    let config = serde_json::from_value(config)
+        .map_err(|err| anyhow::anyhow!("Invalid value `{field}` for `{key}`: {err}"))?;
 
     cx.editor
         .config_events
@@ -1976,7 +2663,9 @@ fn set_option(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> a
 /// syntax.
 /// Example:
 /// -  `:toggle search.smart-case` (bool)
-/// -  `:toggle line-number relative absolute` (string)
+/// -  `:toggle line-number relative absolute` (string, cycles through 2+ values)
+/// -  `:toggle rulers [80] [100] [120]` (list, cycles through 2+ lists)
+/// -  `:toggle lsp display-inlay-hints` (object, toggles a named boolean field)
 fn toggle_option(
     cx: &mut compositor::Context,
     args: Args,
@@ -2041,32 +2730,53 @@ fn toggle_option(
                     .parse()?,
             )
         }
-        Value::Array(value) => {
+        Value::Array(ref current) => {
             let mut parser = args.raw_parser();
             parser.next();
 
-            let mut lists = serde_json::Deserializer::from_str(parser.rest()).into_iter::<Value>();
+            let candidates: Vec<Value> = serde_json::Deserializer::from_str(parser.rest())
+                .into_iter::<Value>()
+                .collect::<Result<_, _>>()?;
 
-            let (Some(first), Some(second)) =
-                (lists.next().transpose()?, lists.next().transpose()?)
-            else {
-                anyhow::bail!(
-                    "Bad arguments. For list configurations use: `:toggle key [...] [...]`",
-                )
-            };
+            ensure!(
+                candidates.len() >= 2 && candidates.iter().all(Value::is_array),
+                "Bad arguments. For list configurations use: `:toggle key [...] [...] ...`",
+            );
 
-            match (&first, &second) {
-                (Value::Array(list), Value::Array(_)) => {
-                    if list == value {
-                        second
-                    } else {
-                        first
-                    }
-                }
-                _ => anyhow::bail!("values must be lists"),
+            let position = candidates
+                .iter()
+                .position(|candidate| candidate.as_array() == Some(current));
+
+            match position {
+                // Found the current list among the candidates: rotate to the next one,
+                // wrapping from the last back to the first.
+                Some(index) => candidates[(index + 1) % candidates.len()].clone(),
+                None => candidates[0].clone(),
             }
         }
-        Value::Null | Value::Object(_) => {
+        Value::Object(ref map) => {
+            let field = args
+                .get(1)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Bad arguments. For object configurations use: `:toggle key field-name`",
+                    )
+                })?
+                .to_lowercase();
+
+            let current = map
+                .get(&field)
+                .ok_or_else(|| anyhow::anyhow!("Unknown field `{field}` in `{key}`"))?;
+
+            let Value::Bool(current) = current else {
+                anyhow::bail!("`{key}.{field}` is not a boolean configuration");
+            };
+
+            let mut map = map.clone();
+            map.insert(field, Value::Bool(!current));
+            Value::Object(map)
+        }
+        Value::Null => {
             anyhow::bail!("Configuration {key} does not support toggle yet")
         }
     };
@@ -2120,12 +2830,80 @@ fn language(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> any
     Ok(())
 }
 
+/// Compares two sort keys by their leading numeric value (so `"2"` sorts before `"10"`),
+/// falling back to a lexical comparison when either side doesn't start with a number.
+fn numeric_sort_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    fn leading_number(s: &str) -> Option<f64> {
+        let trimmed = s.trim_start();
+        let prefix: String = trimmed
+            .char_indices()
+            .take_while(|&(i, c)| c.is_ascii_digit() || ((c == '-' || c == '.') && i == 0))
+            .map(|(_, c)| c)
+            .collect();
+        prefix.parse::<f64>().ok()
+    }
+
+    match (leading_number(a), leading_number(b)) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+/// Extracts the substring a fragment is actually ordered by: the first capture group (or
+/// whole match) of `--key <regex>`, the `--field <n>` whitespace-delimited column, or the
+/// fragment itself when neither was given. Fragments that don't match `--key` get an empty
+/// key, so they sort first (last with `--reverse`).
+fn sort_key<'a>(fragment: &'a str, key: Option<&Regex>, field: Option<usize>) -> &'a str {
+    if let Some(key) = key {
+        return key
+            .captures(fragment)
+            .map(|captures| {
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map_or("", |m| m.as_str())
+            })
+            .unwrap_or("");
+    }
+
+    if let Some(field) = field {
+        return fragment
+            .split_whitespace()
+            .nth(field.saturating_sub(1))
+            .unwrap_or("");
+    }
+
+    fragment
+}
+
 fn sort(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
     if event != PromptEvent::Validate {
         return Ok(());
     }
 
     let reverse = args.has_flag("reverse");
+    let numeric = args.has_flag("numeric");
+    let unique = args.has_flag("unique");
+    let ignore_case = args.has_flag("ignore-case");
+
+    let field = args
+        .get_flag("field")
+        .map(|n| {
+            n.parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| anyhow!("--field expects a positive number"))
+        })
+        .transpose()?;
+    let key = args
+        .get_flag("key")
+        .map(|pattern| Regex::new(pattern).map_err(|err| anyhow!("invalid --key pattern: {err}")))
+        .transpose()?;
+
+    ensure!(
+        key.is_none() || field.is_none(),
+        "--key and --field cannot be used together"
+    );
 
     let scrolloff = cx.editor.config().scrolloff;
     let (view, doc) = current!(cx.editor);
@@ -2133,34 +2911,169 @@ fn sort(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow:
 
     let selection = doc.selection(view.id);
 
-    let mut fragments: Vec<_> = selection
+    let fragments: Vec<Tendril> = selection
         .slices(text)
         .map(|fragment| fragment.chunks().collect())
         .collect();
 
-    fragments.sort_by(match reverse {
-        true => |a: &Tendril, b: &Tendril| b.cmp(a),
-        false => |a: &Tendril, b: &Tendril| a.cmp(b),
+    let mut order: Vec<usize> = (0..fragments.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (ka, kb) = (
+            sort_key(&fragments[a], key.as_ref(), field),
+            sort_key(&fragments[b], key.as_ref(), field),
+        );
+        let ordering = if numeric {
+            numeric_sort_cmp(ka, kb)
+        } else if ignore_case {
+            ka.to_lowercase().cmp(&kb.to_lowercase())
+        } else {
+            ka.cmp(kb)
+        };
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
     });
 
+    let mut removed = 0;
+    let mut previous: Option<&Tendril> = None;
+    let sorted: Vec<Tendril> = order
+        .iter()
+        .map(|&i| &fragments[i])
+        .filter_map(|fragment| {
+            let duplicate = match previous {
+                Some(previous) if ignore_case => {
+                    previous.to_lowercase() == fragment.to_lowercase()
+                }
+                Some(previous) => previous == fragment,
+                None => false,
+            };
+            if unique && duplicate {
+                removed += 1;
+                return None;
+            }
+            previous = Some(fragment);
+            Some(fragment.clone())
+        })
+        .collect();
+
+    // `--unique` shrinks the result, so pad the tail of the selection with deletions to
+    // keep a 1:1 mapping between original ranges and replacement fragments.
+    let replacements = sorted
+        .into_iter()
+        .map(Some)
+        .chain(std::iter::repeat(None).take(removed));
+
+    // A deleted range's line ending (if any) is swallowed along with it, so a removed
+    // duplicate collapses its line entirely instead of leaving a blank line behind.
+    let line_ending_len_at = |pos: usize| match text.get_char(pos) {
+        Some('\r') if text.get_char(pos + 1) == Some('\n') => 2,
+        Some('\r') | Some('\n') => 1,
+        _ => 0,
+    };
+
     let transaction = Transaction::change(
         doc.text(),
-        selection
-            .into_iter()
-            .zip(fragments)
-            .map(|(s, fragment)| (s.from(), s.to(), Some(fragment))),
+        selection.into_iter().zip(replacements).map(|(s, fragment)| match fragment {
+            Some(fragment) => (s.from(), s.to(), Some(fragment)),
+            None => {
+                let end = s.to() + line_ending_len_at(s.to());
+                (s.from(), end, Some(Tendril::from("")))
+            }
+        }),
     );
 
     doc.apply(&transaction, view.id);
     doc.append_changes_to_history(view);
     view.ensure_cursor_in_view(doc, scrolloff);
 
+    if unique && removed > 0 {
+        cx.editor.set_status(format!(
+            "removed {removed} duplicate{}",
+            if removed == 1 { "" } else { "s" }
+        ));
+    }
+
     Ok(())
 }
 
-fn reflow(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
-    if event != PromptEvent::Validate {
-        return Ok(());
+/// Detects a comment marker (`//`, `#`, `*`) or list marker (`-`, `+`, digits followed by
+/// `.` or `)`) at the start of `line`. Returns the line's leading indentation, the marker
+/// text (including one trailing space when present), and whether it's list-style (only the
+/// first wrapped line should keep it) as opposed to comment-style (repeated on every line).
+fn detect_marker(line: &str) -> Option<(&str, &str, bool)> {
+    let indent_len = line.len() - line.trim_start().len();
+    let (indent, rest) = line.split_at(indent_len);
+
+    let (marker_len, is_list) = if rest.starts_with("//") {
+        (2, false)
+    } else if rest.starts_with('#') {
+        (rest.chars().take_while(|&c| c == '#').count(), false)
+    } else if rest.starts_with('*') && !rest.starts_with("**") {
+        (1, false)
+    } else if rest.starts_with('-') || rest.starts_with('+') {
+        (1, true)
+    } else if let Some(end) = rest.find(|c: char| c == '.' || c == ')') {
+        if end > 0 && rest[..end].bytes().all(|b| b.is_ascii_digit()) {
+            (end + 1, true)
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    };
+
+    let mut marker_end = marker_len;
+    if rest[marker_len..].starts_with(' ') {
+        marker_end += 1;
+    }
+
+    Some((indent, &rest[..marker_end], is_list))
+}
+
+/// Reflows `fragment` to `width` columns, preserving a leading comment or list marker
+/// detected from its first line: the marker and its indentation are stripped before
+/// wrapping and re-prepended to every wrapped line. List items hang-indent their
+/// continuation lines under the text instead of repeating the bullet.
+fn reflow_with_marker(fragment: &str, width: usize) -> Tendril {
+    let Some((indent, marker, is_list)) = fragment.lines().next().and_then(detect_marker) else {
+        return helix_core::wrap::reflow_hard_wrap(fragment, width);
+    };
+
+    let prefix = format!("{indent}{marker}");
+    let hanging_prefix = if is_list {
+        " ".repeat(prefix.chars().count())
+    } else {
+        prefix.clone()
+    };
+
+    // Strip each physical line's own marker (or the hanging indent of a continuation
+    // line) so the body can be re-wrapped as a single paragraph.
+    let body = fragment
+        .lines()
+        .map(|line| match detect_marker(line) {
+            Some((_, marker, _)) => line.trim_start()[marker.len()..].trim_start(),
+            None => line.strip_prefix(hanging_prefix.as_str()).unwrap_or(line).trim_start(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let inner_width = width.saturating_sub(prefix.chars().count()).max(1);
+    let wrapped = helix_core::wrap::reflow_hard_wrap(&body, inner_width);
+
+    wrapped
+        .lines()
+        .enumerate()
+        .map(|(i, line)| format!("{}{line}", if i == 0 { &prefix } else { &hanging_prefix }))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .into()
+}
+
+fn reflow(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
+    if event != PromptEvent::Validate {
+        return Ok(());
     }
 
     let scrolloff = cx.editor.config().scrolloff;
@@ -2183,7 +3096,7 @@ fn reflow(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyho
     let selection = doc.selection(view.id);
     let transaction = Transaction::change_by_selection(rope, selection, |range| {
         let fragment = range.fragment(rope.slice(..));
-        let reflowed_text = helix_core::wrap::reflow_hard_wrap(&fragment, text_width);
+        let reflowed_text = reflow_with_marker(&fragment, text_width);
 
         (range.from(), range.to(), Some(reflowed_text))
     });
@@ -2290,8 +3203,7 @@ fn append_output(
         return Ok(());
     }
     ensure!(!args.is_empty(), "Shell command required");
-    shell(cx, &args[0], &ShellBehavior::Append);
-    Ok(())
+    shell_streaming(cx, &args[0], ShellOutputPlacement::After)
 }
 
 fn insert_output(
@@ -2303,32 +3215,134 @@ fn insert_output(
         return Ok(());
     }
     ensure!(!args.is_empty(), "Shell command required");
-    shell(cx, &args[0], &ShellBehavior::Insert);
-    Ok(())
+    shell_streaming(cx, &args[0], ShellOutputPlacement::Before)
 }
 
 fn pipe_to(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
-    pipe_impl(cx, args, event, &ShellBehavior::Ignore)
+    pipe_impl(cx, args, event, ShellOutputPlacement::Discard)
 }
 
 fn pipe(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
-    pipe_impl(cx, args, event, &ShellBehavior::Replace)
+    pipe_impl(cx, args, event, ShellOutputPlacement::Replace)
 }
 
 fn pipe_impl(
     cx: &mut compositor::Context,
     args: Args,
     event: PromptEvent,
-    behavior: &ShellBehavior,
+    placement: ShellOutputPlacement,
 ) -> anyhow::Result<()> {
     if event != PromptEvent::Validate {
         return Ok(());
     }
     ensure!(!args.is_empty(), "Shell command required");
-    shell(cx, &args[0], behavior);
+    shell_streaming(cx, &args[0], placement)
+}
+
+/// What to do with a shell command's captured output, once it has finished running, for each
+/// selection it was run against. Backs [`shell_streaming`].
+#[derive(Clone, Copy)]
+enum ShellOutputPlacement {
+    /// `:insert-output`: insert the output before the selection.
+    Before,
+    /// `:append-output`: insert the output after the selection.
+    After,
+    /// `:pipe`: replace the selection with the output.
+    Replace,
+    /// `:pipe-to`: feed the selection to the command's stdin, but discard its output.
+    Discard,
+}
+
+/// Runs `command_line` once per selection range asynchronously -- feeding the range's text to
+/// the command's stdin for `Replace`/`Discard` placements -- and applies the captured output
+/// per `placement` once every child process has exited, without blocking the main thread on
+/// them the way a synchronous spawn-and-wait would.
+fn shell_streaming(
+    cx: &mut compositor::Context,
+    command_line: &str,
+    placement: ShellOutputPlacement,
+) -> anyhow::Result<()> {
+    let shell = cx.editor.config().shell.clone();
+    let scrolloff = cx.editor.config().scrolloff;
+    let command_line = command_line.to_string();
+    let (view, doc) = current!(cx.editor);
+    let (doc_id, view_id, encoding) = (doc.id(), view.id, doc.encoding());
+    let text = doc.text().clone();
+    let selection = doc.selection(view.id).clone();
+
+    let pipe_input = matches!(
+        placement,
+        ShellOutputPlacement::Replace | ShellOutputPlacement::Discard
+    );
+    let inputs: Vec<Option<String>> = selection
+        .ranges()
+        .iter()
+        .map(|range| pipe_input.then(|| range.fragment(text.slice(..)).to_string()))
+        .collect();
+
+    let callback = async move {
+        let mut outputs = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let output =
+                run_shell_pipe_output(&shell, &command_line, input.as_deref(), encoding).await?;
+            outputs.push(output);
+        }
+
+        let call: job::Callback = Callback::EditorCompositor(Box::new(
+            move |editor: &mut Editor, _compositor: &mut Compositor| {
+                if matches!(placement, ShellOutputPlacement::Discard) {
+                    return;
+                }
+
+                let doc = doc_mut!(editor, &doc_id);
+                let view = view_mut!(editor, view_id);
+                let selection = doc.selection(view.id).clone();
+                let mut outputs = outputs.into_iter();
+
+                let transaction = Transaction::change_by_selection(doc.text(), &selection, |range| {
+                    let output = outputs.next().unwrap_or_default();
+                    match placement {
+                        ShellOutputPlacement::Before => (range.from(), range.from(), Some(output)),
+                        ShellOutputPlacement::After => (range.to(), range.to(), Some(output)),
+                        ShellOutputPlacement::Replace => (range.from(), range.to(), Some(output)),
+                        ShellOutputPlacement::Discard => unreachable!("returned above"),
+                    }
+                });
+
+                doc.apply(&transaction, view.id);
+                doc.append_changes_to_history(view);
+                view.ensure_cursor_in_view(doc, scrolloff);
+            },
+        ));
+        Ok(call)
+    };
+    cx.jobs.callback(callback);
+
     Ok(())
 }
 
+/// Cancellation handles for in-flight `:run-shell-command` invocations, keyed by an
+/// incrementing id, so `:shell-kill` can interrupt one that's taking too long.
+static RUNNING_SHELL_COMMANDS: Lazy<Mutex<HashMap<u64, Arc<Notify>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+static NEXT_SHELL_COMMAND_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Builds and shows (or updates, if already shown) the `:run-shell-command` output popup.
+fn show_shell_output(editor: &mut Editor, compositor: &mut Compositor, output: &str, done: bool) {
+    if output.is_empty() && !done {
+        return;
+    }
+    let contents = ui::Markdown::new(
+        format!("```sh\n{}\n```", output.trim_end()),
+        editor.syn_loader.clone(),
+    );
+    let popup = Popup::new("shell", contents).position(Some(helix_core::Position::new(
+        editor.cursor().0.unwrap_or_default().row,
+        2,
+    )));
+    compositor.replace_or_push("shell", popup);
+}
+
 fn run_shell_command(
     cx: &mut compositor::Context,
     args: Args,
@@ -2339,24 +3353,25 @@ fn run_shell_command(
     }
 
     let shell = cx.editor.config().shell.clone();
+    let command_line = args[0].to_string();
 
-    let args = args[0].to_string();
+    let id = NEXT_SHELL_COMMAND_ID.fetch_add(1, Ordering::Relaxed);
+    let cancel = Arc::new(Notify::new());
+    RUNNING_SHELL_COMMANDS.lock().unwrap().insert(id, cancel.clone());
 
     let callback = async move {
-        let output = shell_impl_async(&shell, &args, None).await?;
+        let result = run_shell_command_streaming(&shell, &command_line, cancel).await;
+        RUNNING_SHELL_COMMANDS.lock().unwrap().remove(&id);
+
+        let (output, cancelled) = result?;
         let call: job::Callback = Callback::EditorCompositor(Box::new(
             move |editor: &mut Editor, compositor: &mut Compositor| {
-                if !output.is_empty() {
-                    let contents = ui::Markdown::new(
-                        format!("```sh\n{}\n```", output.trim_end()),
-                        editor.syn_loader.clone(),
-                    );
-                    let popup = Popup::new("shell", contents).position(Some(
-                        helix_core::Position::new(editor.cursor().0.unwrap_or_default().row, 2),
-                    ));
-                    compositor.replace_or_push("shell", popup);
-                }
-                editor.set_status("Command run");
+                show_shell_output(editor, compositor, &output, true);
+                editor.set_status(if cancelled {
+                    "Command cancelled"
+                } else {
+                    "Command run"
+                });
             },
         ));
         Ok(call)
@@ -2366,6 +3381,94 @@ fn run_shell_command(
     Ok(())
 }
 
+/// Runs `command_line` under `shell`, streaming its combined stdout/stderr into the
+/// `:run-shell-command` popup as it's produced rather than waiting for the process to
+/// exit, and killing it early if `cancel` is notified (by `:shell-kill`). Returns the full
+/// output collected so far and whether the run was cancelled.
+async fn run_shell_command_streaming(
+    shell: &[String],
+    command_line: &str,
+    cancel: Arc<Notify>,
+) -> anyhow::Result<(String, bool)> {
+    let Some((shell_cmd, shell_args)) = shell.split_first() else {
+        bail!("shell config is empty")
+    };
+
+    let mut child = tokio::process::Command::new(shell_cmd)
+        .args(shell_args)
+        .arg(command_line)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to spawn shell command")?;
+
+    let stdout = child.stdout.take().expect("stdout is piped above");
+    let stderr = child.stderr.take().expect("stderr is piped above");
+    let mut stdout = AsyncBufReader::new(stdout).lines();
+    let mut stderr = AsyncBufReader::new(stderr).lines();
+
+    let mut output = String::new();
+    let mut cancelled = false;
+    // Once stderr hits EOF, stop polling it: an un-guarded `stderr.next_line()` would keep
+    // resolving immediately forever, making this branch of the `select!` busy-spin instead of
+    // waiting alongside stdout/cancel for the rest of the command's run.
+    let mut stderr_done = false;
+
+    loop {
+        tokio::select! {
+            line = stdout.next_line() => match line? {
+                Some(line) => {
+                    output.push_str(&line);
+                    output.push('\n');
+                    let snapshot = output.clone();
+                    let _ = job::dispatch(move |editor, compositor| {
+                        show_shell_output(editor, compositor, &snapshot, false);
+                    })
+                    .await;
+                }
+                None => break,
+            },
+            line = stderr.next_line(), if !stderr_done => match line? {
+                Some(line) => {
+                    output.push_str(&line);
+                    output.push('\n');
+                    let snapshot = output.clone();
+                    let _ = job::dispatch(move |editor, compositor| {
+                        show_shell_output(editor, compositor, &snapshot, false);
+                    })
+                    .await;
+                }
+                None => stderr_done = true,
+            },
+            _ = cancel.notified() => {
+                let _ = child.start_kill();
+                cancelled = true;
+                break;
+            }
+        }
+    }
+
+    let _ = child.wait().await;
+
+    Ok((output, cancelled))
+}
+
+fn shell_kill(cx: &mut compositor::Context, _args: Args, event: PromptEvent) -> anyhow::Result<()> {
+    if event != PromptEvent::Validate {
+        return Ok(());
+    }
+
+    let running = RUNNING_SHELL_COMMANDS.lock().unwrap();
+    let Some((_, cancel)) = running.iter().max_by_key(|(&id, _)| id) else {
+        bail!("no shell command is currently running");
+    };
+    cancel.notify_one();
+    cx.editor.set_status("Cancelling running shell command...");
+
+    Ok(())
+}
+
 fn reset_diff_change(
     cx: &mut compositor::Context,
     args: Args,
@@ -2477,16 +3580,84 @@ fn move_buffer(cx: &mut compositor::Context, args: Args, event: PromptEvent) ->
 
     ensure!(args.len() == 1, format!(":move takes one argument"));
 
-    let old_path = doc!(cx.editor)
+    let doc = doc!(cx.editor);
+    let old_path = doc
         .path()
         .context("Scratch buffer cannot be moved. Use :write instead")?
         .clone();
+    let new_path = PathBuf::from(args[0].as_ref());
+
+    if args.has_flag("no-refactor") {
+        if let Err(err) = cx.editor.move_path(&old_path, &new_path) {
+            bail!("Could not move file: {err}");
+        }
+        return Ok(());
+    }
+
+    let old_uri = helix_lsp::Url::from_file_path(&old_path)
+        .map_err(|_| anyhow!("could not build a file URI for {:?}", old_path))?;
+    let new_uri = helix_lsp::Url::from_file_path(&new_path)
+        .map_err(|_| anyhow!("could not build a file URI for {:?}", new_path))?;
 
-    let new_path = &args[0];
+    // Only bother round-tripping through the language servers that actually advertise
+    // `workspace/willRenameFiles` support; everyone else gets a plain move.
+    let clients: Vec<_> = doc
+        .language_servers()
+        .filter(|ls| {
+            ls.capabilities()
+                .workspace
+                .as_ref()
+                .and_then(|workspace| workspace.file_operations.as_ref())
+                .and_then(|file_ops| file_ops.will_rename.as_ref())
+                .is_some()
+        })
+        .cloned()
+        .collect();
 
-    if let Err(err) = cx.editor.move_path(&old_path, new_path.as_ref()) {
-        bail!("Could not move file: {err}");
+    if clients.is_empty() {
+        if let Err(err) = cx.editor.move_path(&old_path, &new_path) {
+            bail!("Could not move file: {err}");
+        }
+        return Ok(());
     }
+
+    let rename = helix_lsp::lsp::FileRename {
+        old_uri: old_uri.to_string(),
+        new_uri: new_uri.to_string(),
+    };
+
+    let callback = async move {
+        let mut edits = Vec::new();
+        for ls in &clients {
+            if let Some(future) = ls.will_rename_files(vec![rename.clone()]) {
+                if let Some(edit) = future.await? {
+                    edits.push((ls.offset_encoding(), edit));
+                }
+            }
+        }
+
+        let call: job::Callback = Callback::EditorCompositor(Box::new(
+            move |editor: &mut Editor, _compositor: &mut Compositor| {
+                if let Err(err) = editor.move_path(&old_path, &new_path) {
+                    editor.set_error(format!("Could not move file: {err}"));
+                    return;
+                }
+
+                for (offset_encoding, edit) in edits {
+                    apply_workspace_edit(editor, offset_encoding, &edit);
+                }
+
+                for ls in &clients {
+                    ls.did_rename_files(vec![rename.clone()]);
+                }
+
+                editor.set_status("Moved file and updated references");
+            },
+        ));
+        Ok(call)
+    };
+    cx.jobs.callback(callback);
+
     Ok(())
 }
 
@@ -2507,42 +3678,355 @@ fn yank_diagnostic(
         None => '+',
     };
 
+    // With no severity flags given, every severity matches.
+    let severities: Vec<&'static str> = [
+        (args.has_flag("error"), "error"),
+        (args.has_flag("warning"), "warning"),
+        (args.has_flag("info"), "info"),
+        (args.has_flag("hint"), "hint"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, name)| enabled.then_some(name))
+    .collect();
+
+    let all = args.has_flag("all");
+    let prefixed = args.has_flag("format");
+
     let (view, doc) = current_ref!(cx.editor);
     let primary = doc.selection(view.id).primary();
 
-    // Look only for diagnostics that intersect with the primary selection
-    let diag: Vec<_> = doc
+    // Look at every diagnostic in the buffer with `--all`, otherwise only those that
+    // intersect with the primary selection.
+    let selected: Vec<_> = doc
         .diagnostics()
         .iter()
-        .filter(|d| primary.overlaps(&helix_core::Range::new(d.range.start, d.range.end)))
-        .map(|d| d.message.clone())
+        .filter(|d| all || primary.overlaps(&helix_core::Range::new(d.range.start, d.range.end)))
+        .filter(|d| {
+            severities.is_empty() || severities.contains(&diagnostic_severity_name(d.severity))
+        })
         .collect();
-    let n = diag.len();
+
+    let n = selected.len();
     if n == 0 {
-        bail!("No diagnostics under primary selection");
+        bail!(if all {
+            "No diagnostics match"
+        } else {
+            "No diagnostics under primary selection"
+        });
     }
 
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    let diag: Vec<String> = selected
+        .iter()
+        .map(|d| {
+            let severity = diagnostic_severity_name(d.severity);
+            *counts.entry(severity).or_insert(0) += 1;
+
+            if !prefixed {
+                return d.message.clone();
+            }
+
+            let source = d.source.as_deref().unwrap_or("-");
+            match d.code.as_ref().map(diagnostic_code_to_string) {
+                Some(code) => format!("{severity}[{code}] {source}: {}", d.message),
+                None => format!("{severity} {source}: {}", d.message),
+            }
+        })
+        .collect();
+
     cx.editor.registers.write(reg, diag)?;
+
+    let breakdown = ["error", "warning", "info", "hint"]
+        .into_iter()
+        .filter_map(|severity| counts.get(severity).map(|count| format!("{count} {severity}")))
+        .collect::<Vec<_>>()
+        .join(", ");
+
     cx.editor.set_status(format!(
-        "Yanked {n} diagnostic{} to register {reg}",
+        "Yanked {n} diagnostic{} ({breakdown}) to register {reg}",
         if n == 1 { "" } else { "s" }
     ));
     Ok(())
 }
 
+fn diagnostic_severity_name(severity: Option<helix_core::diagnostic::Severity>) -> &'static str {
+    use helix_core::diagnostic::Severity;
+    match severity {
+        Some(Severity::Error) | None => "error",
+        Some(Severity::Warning) => "warning",
+        Some(Severity::Info) => "info",
+        Some(Severity::Hint) => "hint",
+    }
+}
+
+fn diagnostic_code_to_string(code: &helix_core::diagnostic::NumberOrString) -> String {
+    use helix_core::diagnostic::NumberOrString;
+    match code {
+        NumberOrString::Number(n) => n.to_string(),
+        NumberOrString::String(s) => s.clone(),
+    }
+}
+
+/// Builds the JSON representation of a single diagnostic shared by both the `json` and
+/// `sarif` `:diagnostics-export` formats.
+fn diagnostic_to_json(path: Option<&Path>, doc: &Document, diagnostic: &helix_core::diagnostic::Diagnostic) -> Value {
+    let text = doc.text().slice(..);
+    let line = text.char_to_line(diagnostic.range.start.min(text.len_chars()));
+    let column = diagnostic.range.start - text.line_to_char(line);
+
+    serde_json::json!({
+        "file": path.map(|path| path.display().to_string()),
+        "line": line + 1,
+        "column": column + 1,
+        "severity": diagnostic_severity_name(diagnostic.severity),
+        "code": diagnostic.code.as_ref().map(diagnostic_code_to_string),
+        "source": diagnostic.source,
+        "message": diagnostic.message,
+    })
+}
+
+/// Wraps already-built `diagnostic_to_json` entries in a minimal SARIF 2.1.0 log, the
+/// subset that CI annotation tools and review dashboards actually read: one `tool.driver`
+/// and one `result` per diagnostic with a physical location and severity level.
+fn diagnostics_to_sarif(entries: &[Value]) -> Value {
+    let results: Vec<Value> = entries
+        .iter()
+        .map(|entry| {
+            let level = match entry["severity"].as_str().unwrap_or("error") {
+                "warning" => "warning",
+                "info" | "hint" => "note",
+                _ => "error",
+            };
+            serde_json::json!({
+                "level": level,
+                "ruleId": entry["code"],
+                "message": { "text": entry["message"] },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": entry["file"] },
+                        "region": {
+                            "startLine": entry["line"],
+                            "startColumn": entry["column"],
+                        },
+                    },
+                }],
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "$schema": "https://raw.githubusercontent.com/oasis-tcs/sarif-spec/master/Schemata/sarif-schema-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": { "name": "helix", "informationUri": "https://helix-editor.com" } },
+            "results": results,
+        }],
+    })
+}
+
+fn diagnostics_export(
+    cx: &mut compositor::Context,
+    args: Args,
+    event: PromptEvent,
+) -> anyhow::Result<()> {
+    if event != PromptEvent::Validate {
+        return Ok(());
+    }
+
+    ensure!(args.len() <= 1, ":diagnostics-export takes at most one output path");
+
+    let format = args.get_flag("format").unwrap_or("json");
+    ensure!(
+        matches!(format, "json" | "sarif"),
+        "--format must be json or sarif"
+    );
+
+    let reg = match args.get_flag("register") {
+        Some(s) => {
+            ensure!(s.chars().count() == 1, format!("Invalid register {s}"));
+            s.chars().next().unwrap()
+        }
+        None => '+',
+    };
+
+    let entries: Vec<Value> = if args.has_flag("all") {
+        cx.editor
+            .documents()
+            .flat_map(|doc| {
+                let path = doc.path().map(PathBuf::as_path);
+                doc.diagnostics()
+                    .iter()
+                    .map(move |d| diagnostic_to_json(path, doc, d))
+            })
+            .collect()
+    } else {
+        let doc = doc!(cx.editor);
+        let path = doc.path().map(PathBuf::as_path);
+        doc.diagnostics()
+            .iter()
+            .map(|d| diagnostic_to_json(path, doc, d))
+            .collect()
+    };
+
+    let n = entries.len();
+    let output = match format {
+        "sarif" => diagnostics_to_sarif(&entries),
+        _ => Value::Array(entries),
+    };
+    let rendered =
+        serde_json::to_string_pretty(&output).context("failed to serialize diagnostics")?;
+
+    match args.first() {
+        Some(path) => {
+            let path = helix_stdx::path::expand_tilde(Path::new(path.as_ref()));
+            std::fs::write(&path, &rendered)
+                .map_err(|err| anyhow!("error writing {:?}: {}", path, err))?;
+            cx.editor.set_status(format!(
+                "Exported {n} diagnostic{} to {:?}",
+                if n == 1 { "" } else { "s" },
+                path
+            ));
+        }
+        None => {
+            cx.editor.registers.write(reg, vec![rendered])?;
+            cx.editor.set_status(format!(
+                "Exported {n} diagnostic{} to register {reg}",
+                if n == 1 { "" } else { "s" }
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `command_line` under `shell` to completion, capturing its stdout, and decodes it
+/// through `encoding` the same way a file read by `:read` would be. Mirrors Vim's `:r !cmd`.
+/// A non-zero exit status is reported as an error rather than inserting partial output.
+async fn read_shell_command_output(
+    shell: &[String],
+    command_line: &str,
+    encoding: &'static encoding_rs::Encoding,
+) -> anyhow::Result<Tendril> {
+    let Some((shell_cmd, shell_args)) = shell.split_first() else {
+        bail!("shell config is empty")
+    };
+
+    let output = tokio::process::Command::new(shell_cmd)
+        .args(shell_args)
+        .arg(command_line)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .context("failed to spawn shell command")?;
+
+    ensure!(
+        output.status.success(),
+        "command exited with {}",
+        output.status
+    );
+
+    let mut reader = BufReader::new(output.stdout.as_slice());
+    let (contents, _, _) = read_to_string(&mut reader, Some(encoding))
+        .map_err(|err| anyhow!("error reading command output: {}", err))?;
+
+    Ok(Tendril::from(contents))
+}
+
+/// Runs `command_line` under `shell` to completion, writing `input` (if any) to its stdin and
+/// decoding its stdout through `encoding` the same way [`read_shell_command_output`] does.
+/// Used by [`shell_streaming`] to back `:insert-output`/`:append-output`/`:pipe`/`:pipe-to`
+/// without blocking the main thread on the child process.
+async fn run_shell_pipe_output(
+    shell: &[String],
+    command_line: &str,
+    input: Option<&str>,
+    encoding: &'static encoding_rs::Encoding,
+) -> anyhow::Result<Tendril> {
+    let Some((shell_cmd, shell_args)) = shell.split_first() else {
+        bail!("shell config is empty")
+    };
+
+    let mut child = tokio::process::Command::new(shell_cmd)
+        .args(shell_args)
+        .arg(command_line)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to spawn shell command")?;
+
+    if let Some(input) = input {
+        let mut stdin = child.stdin.take().expect("stdin is piped above");
+        stdin.write_all(input.as_bytes()).await?;
+        drop(stdin);
+    }
+
+    let output = child
+        .wait_with_output()
+        .await
+        .context("failed waiting for shell command")?;
+
+    ensure!(
+        output.status.success(),
+        "command exited with {}",
+        output.status
+    );
+
+    let mut reader = BufReader::new(output.stdout.as_slice());
+    let (contents, _, _) = read_to_string(&mut reader, Some(encoding))
+        .map_err(|err| anyhow!("error reading command output: {}", err))?;
+
+    Ok(Tendril::from(contents))
+}
+
 fn read(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
     if event != PromptEvent::Validate {
         return Ok(());
     }
 
+    ensure!(!args.is_empty(), "file name is expected");
+
+    let arg = args.first().unwrap();
+
+    if let Some(command_line) = arg.strip_prefix('!') {
+        ensure!(!command_line.is_empty(), "shell command is expected after !");
+
+        let shell = cx.editor.config().shell.clone();
+        let command_line = command_line.to_string();
+        let (view, doc) = current!(cx.editor);
+        let (doc_id, view_id, encoding) = (doc.id(), view.id, doc.encoding());
+
+        let callback = async move {
+            let contents = read_shell_command_output(&shell, &command_line, encoding).await?;
+            let call: job::Callback = Callback::EditorCompositor(Box::new(
+                move |editor: &mut Editor, _compositor: &mut Compositor| {
+                    let scrolloff = editor.config().scrolloff;
+                    let doc = doc_mut!(editor, &doc_id);
+                    let view = view_mut!(editor, view_id);
+                    let selection = doc.selection(view.id).clone();
+                    let transaction = Transaction::insert(doc.text(), &selection, contents);
+                    doc.apply(&transaction, view.id);
+                    doc.append_changes_to_history(view);
+                    view.ensure_cursor_in_view(doc, scrolloff);
+                },
+            ));
+            Ok(call)
+        };
+        cx.jobs.callback(callback);
+
+        return Ok(());
+    }
+
     let scrolloff = cx.editor.config().scrolloff;
     let (view, doc) = current!(cx.editor);
 
-    ensure!(!args.is_empty(), "file name is expected");
-    ensure!(args.len() == 1, "only the file name is expected");
-
-    let path =
-        helix_stdx::path::expand_tilde(Path::new(args.first().map(|path| path.as_ref()).unwrap()));
+    let path = helix_stdx::path::expand_tilde(Path::new(arg.as_ref()));
 
     ensure!(
         path.exists() && path.is_file(),
@@ -2742,12 +4226,26 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
                     desc: "skips formatting when saving buffer",
                     accepts: None,
                     completer: None,
+                },
+                Flag {
+                    long: "backup",
+                    short: None,
+                    desc: "backs up the previous contents to `file~` before replacing it",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "no-atomic",
+                    short: None,
+                    desc: "writes directly to the target file instead of via a temporary file and rename",
+                    accepts: None,
+                    completer: None,
                 }
             ],
             accepts: Some("<path>"),
             positionals: (0, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::filename])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::filename)])
          },
         doc: "write the current buffer to its file or to <path> if specified",
         fun: write,
@@ -2763,12 +4261,26 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
                     desc: "skips formatting when saving buffer",
                     accepts: None,
                     completer: None,
+                },
+                Flag {
+                    long: "backup",
+                    short: None,
+                    desc: "backs up the previous contents to `file~` before replacing it",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "no-atomic",
+                    short: None,
+                    desc: "writes directly to the target file instead of via a temporary file and rename",
+                    accepts: None,
+                    completer: None,
                 }
             ],
             accepts: Some("<path>"),
             positionals: (0, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::filename])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::filename)])
          },
         doc: "Force write changes to disk creating necessary subdirectories. Accepts an optional path (:write! some/path.txt)",
         fun: force_write,
@@ -2789,7 +4301,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<path>"),
             positionals: (0, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::filename])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::filename)])
          },
         doc: "Write changes to disk and closes the buffer. Accepts an optional path (:write-buffer-close some/path.txt)",
         fun: write_buffer_close,
@@ -2810,7 +4322,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<path>"),
             positionals: (0, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::filename])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::filename)])
          },
         doc: "Force write changes to disk creating necessary subdirectories and closes the buffer. Accepts an optional path (:write-buffer-close! some/path.txt)",
         fun: force_write_buffer_close,
@@ -2841,6 +4353,19 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
         doc: "Format the file using an external formatter or language server.",
         fun: format,
     },
+    TypableCommand {
+        name: "indent",
+        aliases: &[],
+        signature: CommandSignature {
+            flags: &[],
+            accepts: None,
+            positionals: (0, Some(0)),
+            parse_mode: ParseMode::Parameters,
+            completer: CommandCompleter::none()
+         },
+        doc: "Indent the lines touched by the current selection by one level.",
+        fun: indent,
+    },
     TypableCommand {
         name: "indent-style",
         aliases: &[],
@@ -2849,20 +4374,20 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<indent>"),
             positionals: (0, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::none()
+            completer: CommandCompleter::values(INDENT_STYLE_VALUES)
          },
         doc: "Set the indentation style for editing. ('t' for tabs or 1-16 for number of spaces.)",
         fun: set_indent_style,
     },
     TypableCommand {
         name: "line-ending",
-        aliases: &[],
+        aliases: &["set-line-ending"],
         signature: CommandSignature {
             flags: &[],
             accepts: Some("<line-ending>"),
-            positionals: (1, Some(1)),
+            positionals: (0, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::none()
+            completer: CommandCompleter::values(LINE_ENDING_VALUES)
          },
         #[cfg(not(feature = "unicode-lines"))]
         doc: "Set the document's default line ending. Options: crlf, lf.",
@@ -2912,7 +4437,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<path>"),
             positionals: (0, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::filename])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::filename)])
          },
         doc: "Write changes to disk and close the current view. Accepts an optional path (:wq some/path.txt)",
         fun: write_quit,
@@ -2933,7 +4458,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<path>"),
             positionals: (0, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::filename])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::filename)])
          },
         doc: "Write changes to disk and close the current view forcefully. Accepts an optional path (:wq! some/path.txt)",
         fun: force_write_quit,
@@ -3078,15 +4603,41 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
         name: "theme",
         aliases: &[],
         signature: CommandSignature {
-            flags: &[],
+            flags: &[Flag {
+                long: "auto",
+                short: None,
+                desc: "follow the OS/terminal's light or dark appearance, switching between <light-theme> and <dark-theme>",
+                accepts: None,
+                completer: None,
+            }],
             accepts: Some("<theme>"),
-            positionals: (1, Some(1)),
+            positionals: (0, Some(2)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::theme])
+            completer: CommandCompleter::positional(&[
+                ArgCompleter::Function(completers::theme),
+                ArgCompleter::Function(completers::theme),
+            ])
          },
-        doc: "Change the editor theme (show current theme if no name specified).",
+        doc: "Change the editor theme (show current theme if no name specified). With \
+              `--auto <light-theme> <dark-theme>`, follow the OS/terminal's appearance instead.",
         fun: theme,
     },
+    TypableCommand {
+        name: "theme-auto",
+        aliases: &[],
+        signature: CommandSignature {
+            flags: &[],
+            accepts: Some("<light-theme> <dark-theme>"),
+            positionals: (2, Some(2)),
+            parse_mode: ParseMode::Parameters,
+            completer: CommandCompleter::positional(&[
+                ArgCompleter::Function(completers::theme),
+                ArgCompleter::Function(completers::theme),
+            ])
+         },
+        doc: "Switch between two themes automatically, following the OS or terminal's light/dark appearance.",
+        fun: theme_auto,
+    },
     TypableCommand {
         name: "yank-join",
         aliases: &[],
@@ -3251,7 +4802,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<directory>"),
             positionals: (1, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::directory])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::directory)])
          },
         doc: "Change the current working directory.",
         fun: change_current_directory,
@@ -3339,12 +4890,12 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
         aliases: &[],
         signature: CommandSignature {
             flags: &[],
-            accepts: None,
-            positionals: (0, Some(0)),
+            accepts: Some("<command> [argument]..."),
+            positionals: (0, None),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::lsp_workspace_command])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::lsp_workspace_command)])
          },
-        doc: "Open workspace command picker",
+        doc: "Open workspace command picker, or run <command> with the given arguments directly.",
         fun: lsp_workspace_command,
     },
     TypableCommand {
@@ -3527,7 +5078,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<language>"),
             positionals: (1, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::language])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::language)])
          },
         doc: "Set the language of current buffer (show current language if no value specified).",
         fun: language,
@@ -3535,13 +5086,15 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
     TypableCommand {
         name: "set-option",
         aliases: &["set"],
-        // TODO: Add support for completion of the options value(s), when appropriate.
         signature: CommandSignature {
             flags: &[],
             accepts: Some("<option> <value>"),
             positionals: (2, Some(2)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::setting])
+            completer: CommandCompleter::positional(&[
+                ArgCompleter::Function(completers::setting),
+                ArgCompleter::ConfigValue,
+            ])
          },
         doc: "Set a config option at runtime.\nFor example to disable smart case search, use `:set search.smart-case false`.",
         fun: set_option,
@@ -3554,10 +5107,12 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<option> <value> <value>"),
             positionals: (1, None),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::setting])
+            completer: CommandCompleter::positional(&[
+                ArgCompleter::Function(completers::setting),
+                ArgCompleter::ConfigValue,
+            ])
          },
-        // TODO: Not just blooleans
-        doc: "Toggle a boolean config option at runtime.\nFor example to toggle smart case search, use `:toggle search.smart-case`.",
+        doc: "Toggle a config option at runtime. Booleans flip, and a list of values after the key cycles through them (wrapping around); lists and objects are supported too.\nFor example to toggle smart case search, use `:toggle search.smart-case`.",
         fun: toggle_option,
     },
     TypableCommand {
@@ -3568,7 +5123,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<option>"),
             positionals: (1, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::setting])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::setting)])
          },
         doc: "Get the current value of a config option.",
         fun: get_option,
@@ -3584,6 +5139,41 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
                     desc: "reverses sort order",
                     accepts: None,
                     completer: None,
+                },
+                Flag {
+                    long: "numeric",
+                    short: Some("n"),
+                    desc: "sorts by leading numeric value instead of lexically",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "unique",
+                    short: Some("u"),
+                    desc: "drops duplicate fragments after sorting",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "ignore-case",
+                    short: Some("i"),
+                    desc: "compares sort keys case-insensitively",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "key",
+                    short: None,
+                    desc: "sorts by a regex capture group instead of the whole fragment",
+                    accepts: Some("<regex>"),
+                    completer: None,
+                },
+                Flag {
+                    long: "field",
+                    short: None,
+                    desc: "sorts by the nth whitespace-delimited column instead of the whole fragment",
+                    accepts: Some("<n>"),
+                    completer: None,
                 }
             ],
             accepts: None,
@@ -3604,9 +5194,22 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             parse_mode: ParseMode::Parameters,
             completer: CommandCompleter::none()
          },
-        doc: "Hard-wrap the current selection of lines to a given width.",
+        doc: "Hard-wrap the current selection of lines to a given width, preserving comment and list markers.",
         fun: reflow,
     },
+    TypableCommand {
+        name: "mark",
+        aliases: &[],
+        signature: CommandSignature {
+            flags: &[],
+            accepts: Some("<name>"),
+            positionals: (1, Some(1)),
+            parse_mode: ParseMode::Parameters,
+            completer: CommandCompleter::none()
+         },
+        doc: "Set mark <name> (a single character) to the current line, for use as a 'name endpoint in a range prefix (e.g. :'a,'b reflow).",
+        fun: mark,
+    },
     TypableCommand {
         name: "tree-sitter-subtree",
         aliases: &["ts-subtree"],
@@ -3680,7 +5283,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<command>"),
             positionals: (1, Some(1)),
             parse_mode: ParseMode::Literal,
-            completer: CommandCompleter::none()
+            completer: CommandCompleter::executable()
          },
         doc: "Run shell command, inserting output before each selection.",
         fun: insert_output,
@@ -3693,7 +5296,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<command>"),
             positionals: (1, Some(1)),
             parse_mode: ParseMode::Literal,
-            completer: CommandCompleter::none()
+            completer: CommandCompleter::executable()
          },
         doc: "Run shell command, appending output after each selection.",
         fun: append_output,
@@ -3706,7 +5309,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<command>"),
             positionals: (1, Some(1)),
             parse_mode: ParseMode::Literal,
-            completer: CommandCompleter::none()
+            completer: CommandCompleter::executable()
          },
         doc: "Pipe each selection to the shell command.",
         fun: pipe,
@@ -3719,7 +5322,7 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
             accepts: Some("<command>"),
             positionals: (1, Some(1)),
             parse_mode: ParseMode::Literal,
-            completer: CommandCompleter::none()
+            completer: CommandCompleter::executable()
          },
         doc: "Pipe each selection to the shell command, ignoring output.",
         fun: pipe_to,
@@ -3727,17 +5330,29 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
     TypableCommand {
         name: "run-shell-command",
         aliases: &["sh"],
-        // TODO: Is this right? path completions?
         signature: CommandSignature {
             flags: &[],
             accepts: Some("<command>"),
             positionals: (1, Some(1)),
             parse_mode: ParseMode::Literal,
-            completer: CommandCompleter::all(completers::filename)
+            completer: CommandCompleter::executable()
          },
-        doc: "Run a shell command",
+        doc: "Run a shell command, streaming its output live. Cancel it with :shell-kill.",
         fun: run_shell_command,
     },
+    TypableCommand {
+        name: "shell-kill",
+        aliases: &[],
+        signature: CommandSignature {
+            flags: &[],
+            accepts: None,
+            positionals: (0, Some(0)),
+            parse_mode: ParseMode::Parameters,
+            completer: CommandCompleter::none()
+         },
+        doc: "Cancel the most recently started :run-shell-command.",
+        fun: shell_kill,
+    },
     TypableCommand {
         name: "reset-diff-change",
         aliases: &["diffget", "diffg"],
@@ -3789,20 +5404,69 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
         name: "move",
         aliases: &["mv"],
         signature: CommandSignature {
-            flags: &[],
+            flags: &[Flag {
+                long: "no-refactor",
+                short: None,
+                desc: "skip notifying language servers, so references to the old path are not updated",
+                accepts: None,
+                completer: None,
+            }],
             accepts: Some("<path>"),
             positionals: (1, Some(1)),
             parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::filename])
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::filename)])
          },
-        doc: "Move the current buffer and its corresponding file to a different path",
+        doc: "Move the current buffer and its corresponding file to a different path, updating references via the language server",
         fun: move_buffer,
     },
     TypableCommand {
         name: "yank-diagnostic",
         aliases: &[],
         signature: CommandSignature {
-            flags: &[],
+            flags: &[
+                Flag {
+                    long: "all",
+                    short: None,
+                    desc: "yank every diagnostic in the buffer instead of only the primary selection",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "error",
+                    short: None,
+                    desc: "only yank error diagnostics",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "warning",
+                    short: None,
+                    desc: "only yank warning diagnostics",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "info",
+                    short: None,
+                    desc: "only yank info diagnostics",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "hint",
+                    short: None,
+                    desc: "only yank hint diagnostics",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "format",
+                    short: None,
+                    desc: "prefix each line with its severity, code, and source, e.g. `error[E0382] rustc: ...`",
+                    accepts: None,
+                    completer: None,
+                },
+            ],
             accepts: None,
             positionals: (0, Some(1)),
             parse_mode: ParseMode::Parameters,
@@ -3811,19 +5475,89 @@ pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
         doc: "Yank diagnostic(s) under primary cursor to register, or clipboard by default",
         fun: yank_diagnostic,
     },
+    TypableCommand {
+        name: "diagnostics-export",
+        aliases: &[],
+        signature: CommandSignature {
+            flags: &[
+                Flag {
+                    long: "format",
+                    short: None,
+                    desc: "output format: json (default) or sarif",
+                    accepts: Some("<format>"),
+                    completer: Some(ArgCompleter::Values(&["json", "sarif"])),
+                },
+                Flag {
+                    long: "all",
+                    short: None,
+                    desc: "export diagnostics from every open buffer instead of just the current one",
+                    accepts: None,
+                    completer: None,
+                },
+                Flag {
+                    long: "register",
+                    short: None,
+                    desc: "register to write to when no output path is given (default '+')",
+                    accepts: Some("<register>"),
+                    completer: None,
+                },
+            ],
+            accepts: Some("<path>"),
+            positionals: (0, Some(1)),
+            parse_mode: ParseMode::Parameters,
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::filename)])
+         },
+        doc: "Export diagnostics as JSON or SARIF to a file, or a register if no path is given",
+        fun: diagnostics_export,
+    },
     TypableCommand {
         name: "read",
         aliases: &["r"],
         signature: CommandSignature {
             flags: &[],
-            accepts: Some("<path>"),
+            accepts: Some("<path>|!<command>"),
             positionals: (1, Some(1)),
-            parse_mode: ParseMode::Parameters,
-            completer: CommandCompleter::positional(&[completers::filename])
+            // The whole remainder of the line is captured as one argument so that
+            // `:r !git log --oneline` keeps its spaces instead of being split into
+            // positionals that fail the arity check.
+            parse_mode: ParseMode::Literal,
+            completer: CommandCompleter::positional(&[ArgCompleter::Function(completers::filename)])
          },
-        doc: "Load a file into buffer",
+        doc: "Load a file into buffer, or insert the output of `!<command>`",
         fun: read,
     },
+    TypableCommand {
+        name: "define-command",
+        aliases: &["def"],
+        signature: CommandSignature {
+            flags: &[Flag {
+                long: "list",
+                short: None,
+                desc: "list every user-defined command",
+                accepts: None,
+                completer: None,
+            }],
+            accepts: Some("<name> <command>..."),
+            positionals: (0, None),
+            parse_mode: ParseMode::Parameters,
+            completer: CommandCompleter::none()
+         },
+        doc: "Define a command that runs a sequence of other commands, e.g. `:def sq ':write' ':quit'`. Builtin command names cannot be redefined.",
+        fun: define_command,
+    },
+    TypableCommand {
+        name: "undefine-command",
+        aliases: &["undef"],
+        signature: CommandSignature {
+            flags: &[],
+            accepts: Some("<name>"),
+            positionals: (1, Some(1)),
+            parse_mode: ParseMode::Parameters,
+            completer: CommandCompleter::none()
+         },
+        doc: "Remove a command previously registered with :define-command",
+        fun: undefine_command,
+    },
 ];
 
 pub static TYPABLE_COMMAND_MAP: Lazy<HashMap<&'static str, &'static TypableCommand>> =
@@ -3837,102 +5571,659 @@ pub static TYPABLE_COMMAND_MAP: Lazy<HashMap<&'static str, &'static TypableComma
             .collect()
     });
 
+/// Runtime registry for `:define-command`, layered over the static `TYPABLE_COMMAND_MAP`.
+/// Maps a user-chosen name to the sequence of command lines it expands to, e.g.
+/// `:def sq ':write' ':quit'` registers `sq` -> `["write", "quit"]`. Names of builtins
+/// (including their aliases, such as `wq`) are refused by `define_command`.
+static USER_COMMANDS: Lazy<Mutex<HashMap<String, Vec<String>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A user-defined command that (directly or transitively) invokes itself would otherwise
+/// overflow the stack; `USER_COMMAND_DEPTH` tracks how deep the current dispatch chain is
+/// so `run_user_command` can refuse past this depth instead.
+const MAX_USER_COMMAND_DEPTH: usize = 8;
+
+thread_local! {
+    static USER_COMMAND_DEPTH: Cell<usize> = Cell::new(0);
+}
+
+/// Installs `definitions` into the runtime user-command registry, e.g. from a `[commands]`
+/// table read at startup. Skips any name that collides with a builtin rather than shadowing
+/// it, since builtins are always consulted first by `dispatch_command_line`.
+pub(crate) fn load_user_commands(definitions: impl IntoIterator<Item = (String, Vec<String>)>) {
+    let mut registry = USER_COMMANDS.lock().unwrap();
+    for (name, steps) in definitions {
+        if TYPABLE_COMMAND_MAP.contains_key(name.as_str()) {
+            log::warn!("ignoring user-defined command `{name}`: shadows a builtin");
+            continue;
+        }
+        registry.insert(name, steps);
+    }
+}
+
+/// Runtime registry for command aliases, filled only through [`load_command_aliases`] (this
+/// file does not read a config table itself), layered over `TYPABLE_COMMAND_MAP` like
+/// `USER_COMMANDS` but distinct from it: an alias's steps are templates re-expanded against the invoking `Args` and editor state
+/// on every run (see [`expand_alias_step`]), rather than a fixed sequence of command lines
+/// replayed verbatim with no access to arguments.
+static COMMAND_ALIASES: Lazy<Mutex<HashMap<String, Vec<String>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Matches `%arg{N}`/`%arg{N..}` and `%{buffer_name}`/`%{line_number}`/`%{selection}`
+/// placeholders inside an alias step template.
+static ALIAS_PLACEHOLDER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"%arg\{(\d+)(\.\.)?\}|%\{(buffer_name|line_number|selection)\}").unwrap()
+});
+
+/// Matches the start of any `%arg{`/`%{` placeholder, valid or not, so
+/// [`validate_alias_step`] can tell a malformed placeholder from a well-formed one.
+static ALIAS_PLACEHOLDER_START: Lazy<Regex> = Lazy::new(|| Regex::new(r"%arg\{|%\{").unwrap());
+
+/// Checks that `step`'s command name is an existing builtin and that every placeholder in it
+/// is well-formed, so malformed or dangling aliases are rejected at load time instead of
+/// failing confusingly the first time they're invoked.
+fn validate_alias_step(step: &str) -> anyhow::Result<()> {
+    let shellwords = Shellwords::from(step);
+    let command = shellwords.command();
+    ensure!(
+        TYPABLE_COMMAND_MAP.contains_key(command),
+        "alias step `{step}` does not name an existing command"
+    );
+
+    let opens = ALIAS_PLACEHOLDER_START.find_iter(step).count();
+    let valid = ALIAS_PLACEHOLDER.find_iter(step).count();
+    ensure!(
+        opens == valid,
+        "alias step `{step}` has malformed placeholder syntax"
+    );
+
+    Ok(())
+}
+
+/// Installs `aliases` into the runtime alias registry, e.g. from a `[command-aliases]` table
+/// read at startup. Skips (and logs) any alias that collides with a builtin or fails
+/// [`validate_alias_step`] rather than refusing to start.
+pub(crate) fn load_command_aliases(aliases: impl IntoIterator<Item = (String, Vec<String>)>) {
+    let mut registry = COMMAND_ALIASES.lock().unwrap();
+    for (name, steps) in aliases {
+        if TYPABLE_COMMAND_MAP.contains_key(name.as_str()) {
+            log::warn!("ignoring command alias `{name}`: shadows a builtin");
+            continue;
+        }
+
+        if let Err(err) = steps.iter().try_for_each(|step| validate_alias_step(step)) {
+            log::warn!("ignoring command alias `{name}`: {err}");
+            continue;
+        }
+
+        registry.insert(name, steps);
+    }
+}
+
+/// Substitutes the `%arg{N}`/`%arg{N..}` and `%{buffer_name}`/`%{line_number}`/`%{selection}`
+/// placeholders in one alias step template, using `args` (the invoking command's arguments)
+/// and the current document.
+fn expand_alias_step(
+    cx: &mut compositor::Context,
+    template: &str,
+    args: &Args,
+) -> anyhow::Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut last_end = 0;
+
+    for caps in ALIAS_PLACEHOLDER.captures_iter(template) {
+        let whole = caps.get(0).unwrap();
+        out.push_str(&template[last_end..whole.start()]);
+
+        if let Some(index) = caps.get(1) {
+            let index: usize = index.as_str().parse().unwrap();
+            if caps.get(2).is_some() {
+                out.push_str(&args.get(index..).unwrap_or_default().join(" "));
+            } else {
+                let value = args.get(index).ok_or_else(|| {
+                    anyhow!(
+                        "alias references %arg{{{index}}} but only {} argument(s) were given",
+                        args.len()
+                    )
+                })?;
+                out.push_str(value);
+            }
+        } else if let Some(var) = caps.get(3) {
+            match var.as_str() {
+                "buffer_name" => out.push_str(&doc!(cx.editor).display_name()),
+                "line_number" => {
+                    let (view, doc) = current_ref!(cx.editor);
+                    let text = doc.text();
+                    let cursor = doc.selection(view.id).primary().cursor(text.slice(..));
+                    write!(out, "{}", text.char_to_line(cursor) + 1)?;
+                }
+                "selection" => {
+                    let (view, doc) = current_ref!(cx.editor);
+                    let text = doc.text();
+                    let fragment = doc.selection(view.id).primary().fragment(text.slice(..));
+                    out.push_str(&fragment);
+                }
+                _ => unreachable!("ALIAS_PLACEHOLDER only matches known variable names"),
+            }
+        }
+
+        last_end = whole.end();
+    }
+
+    out.push_str(&template[last_end..]);
+    Ok(out)
+}
+
+/// Expands and runs `name`'s registered alias steps against the invoking `args`: each step's
+/// placeholders are substituted, the result is re-parsed through `Shellwords`, and dispatched
+/// like an ordinary command line. Stops on (and propagates) the first step that errors.
+fn run_command_alias(
+    cx: &mut compositor::Context,
+    name: &str,
+    args: &Args,
+    event: PromptEvent,
+) -> anyhow::Result<()> {
+    let steps = COMMAND_ALIASES
+        .lock()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("no such command: '{name}'"))?;
+
+    steps.iter().try_for_each(|step| {
+        let expanded = expand_alias_step(cx, step, args)?;
+        dispatch_command_line(cx, &expanded, event)
+    })
+}
+
+/// Runs `name`'s registered steps in order through [`dispatch_command_line`], stopping (and
+/// propagating) on the first step that errors.
+fn run_user_command(
+    cx: &mut compositor::Context,
+    name: &str,
+    event: PromptEvent,
+) -> anyhow::Result<()> {
+    let steps = USER_COMMANDS
+        .lock()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("no such command: '{name}'"))?;
+
+    let depth = USER_COMMAND_DEPTH.with(Cell::get);
+    ensure!(
+        depth < MAX_USER_COMMAND_DEPTH,
+        "`{name}` recursed past the maximum depth of {MAX_USER_COMMAND_DEPTH}"
+    );
+
+    USER_COMMAND_DEPTH.with(|cell| cell.set(depth + 1));
+    let result = steps
+        .iter()
+        .try_for_each(|step| dispatch_command_line(cx, step, event));
+    USER_COMMAND_DEPTH.with(|cell| cell.set(depth));
+
+    result
+}
+
+/// One endpoint of a Vim-style `:10,20 command` range prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RangeEndpoint {
+    /// A literal line number, as typed (1-indexed).
+    Absolute(usize),
+    /// `.`: the current line.
+    Current,
+    /// `$`: the last line of the document.
+    Last,
+    /// `+N`/`-N`: relative to the current line.
+    Relative(isize),
+    /// `'x`: a named mark.
+    Mark(char),
+}
+
+impl RangeEndpoint {
+    /// Parses a single endpoint from the front of `s` (`.`, `$`, `+5`, `-2`, `'a`, or a bare
+    /// line number), returning the endpoint along with whatever of `s` wasn't consumed.
+    /// Returns `None` if `s` doesn't start with a recognized endpoint, so the caller can fall
+    /// back to treating the whole thing as an ordinary command name.
+    fn parse_prefix(s: &str) -> Option<(Self, &str)> {
+        if let Some(rest) = s.strip_prefix('.') {
+            return Some((Self::Current, rest));
+        }
+        if let Some(rest) = s.strip_prefix('$') {
+            return Some((Self::Last, rest));
+        }
+        if let Some(rest) = s.strip_prefix('\'') {
+            let mark = rest.chars().next()?;
+            return Some((Self::Mark(mark), &rest[mark.len_utf8()..]));
+        }
+
+        let (sign, digits) = if let Some(rest) = s.strip_prefix('+') {
+            (1, rest)
+        } else if let Some(rest) = s.strip_prefix('-') {
+            (-1, rest)
+        } else {
+            (0, s)
+        };
+
+        let digit_len = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        if digit_len == 0 {
+            return None;
+        }
+        let n: isize = digits[..digit_len].parse().ok()?;
+
+        let endpoint = if sign == 0 {
+            Self::Absolute(n as usize)
+        } else {
+            Self::Relative(sign * n)
+        };
+        Some((endpoint, &digits[digit_len..]))
+    }
+}
+
+/// Peels a leading Vim-style range prefix (`10,20`, `.,+5`, `'a,'b`, or `%`) off of `input`, a
+/// `:`-command line. The range doesn't have to be its own `Shellwords` token: as in Vim, both
+/// `:10,20 sort` and the glued `:10,20sort`/`:%y` spellings are recognized. Returns the parsed
+/// endpoints and the number of leading bytes of `input` they consumed, or `None` when `input`
+/// doesn't start with a range, so `dispatch_command_line` falls through to the existing
+/// `:NUMBER` goto and builtin lookup.
+fn parse_range_prefix(input: &str) -> Option<(RangeEndpoint, RangeEndpoint, usize)> {
+    if let Some(rest) = input.strip_prefix('%') {
+        return Some((RangeEndpoint::Absolute(1), RangeEndpoint::Last, input.len() - rest.len()));
+    }
+
+    let (start, rest) = RangeEndpoint::parse_prefix(input)?;
+    let rest = rest.strip_prefix(',')?;
+    let (end, rest) = RangeEndpoint::parse_prefix(rest)?;
+    Some((start, end, input.len() - rest.len()))
+}
+
+/// Per-document named marks set by `:mark`, consulted when resolving a `'x` range endpoint.
+static MARKS: Lazy<Mutex<HashMap<(DocumentId, char), usize>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Resolves a parsed range against the current document into a char-offset [`Range`],
+/// spanning whole lines. Reversed endpoints are swapped and out-of-bounds lines are clamped
+/// to the document.
+fn resolve_line_range(
+    cx: &mut compositor::Context,
+    start: RangeEndpoint,
+    end: RangeEndpoint,
+) -> anyhow::Result<helix_core::Range> {
+    let (view, doc) = current_ref!(cx.editor);
+    let text = doc.text();
+    let last_line = text.len_lines();
+    let current_line = text.char_to_line(doc.selection(view.id).primary().cursor(text.slice(..))) + 1;
+    let doc_id = doc.id();
+
+    let resolve = |endpoint: RangeEndpoint| -> anyhow::Result<usize> {
+        let line = match endpoint {
+            RangeEndpoint::Absolute(n) => n,
+            RangeEndpoint::Current => current_line,
+            RangeEndpoint::Last => last_line,
+            RangeEndpoint::Relative(delta) => current_line.saturating_add_signed(delta).max(1),
+            RangeEndpoint::Mark(name) => MARKS
+                .lock()
+                .unwrap()
+                .get(&(doc_id, name))
+                .copied()
+                .with_context(|| format!("mark '{name}' is not set"))?,
+        };
+        Ok(line.clamp(1, last_line))
+    };
+
+    let (mut start_line, mut end_line) = (resolve(start)?, resolve(end)?);
+    if start_line > end_line {
+        mem::swap(&mut start_line, &mut end_line);
+    }
+
+    let start_char = text.line_to_char(start_line - 1);
+    let end_char = text.line_to_char(end_line);
+    Ok(helix_core::Range::new(start_char, end_char))
+}
+
+/// Typable commands whose `fun` reads the current selection and so can meaningfully operate
+/// on an explicit `:10,20 command`-style range instead: the range is installed as the primary
+/// selection before the command runs. Commands not in this list ignore a leading range.
+const RANGE_AWARE_COMMANDS: &[&str] = &["sort", "reflow", "indent"];
+
+/// Shifts every line touched by the current selection one level deeper, using the document's
+/// configured indent unit (`doc.indent_style`). The typable counterpart of the `>` keybinding,
+/// added so a range prefix like `:10,20 indent` has something to dispatch to.
+fn indent(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
+    if event != PromptEvent::Validate {
+        return Ok(());
+    }
+    ensure!(args.is_empty(), ":indent takes no arguments");
+
+    let scrolloff = cx.editor.config().scrolloff;
+    let (view, doc) = current!(cx.editor);
+    let text = doc.text();
+    let indent_unit = Tendril::from(doc.indent_style.as_str());
+
+    let mut lines: Vec<usize> = doc
+        .selection(view.id)
+        .line_ranges(text.slice(..))
+        .flatten()
+        .collect();
+    lines.sort_unstable();
+    lines.dedup();
+
+    let transaction = Transaction::change(
+        doc.text(),
+        lines.into_iter().map(|line| {
+            let pos = text.line_to_char(line);
+            (pos, pos, Some(indent_unit.clone()))
+        }),
+    );
+
+    doc.apply(&transaction, view.id);
+    doc.append_changes_to_history(view);
+    view.ensure_cursor_in_view(doc, scrolloff);
+
+    Ok(())
+}
+
+fn mark(cx: &mut compositor::Context, args: Args, event: PromptEvent) -> anyhow::Result<()> {
+    if event != PromptEvent::Validate {
+        return Ok(());
+    }
+
+    ensure!(args.len() == 1, ":mark takes one argument");
+    let name = args[0].as_ref();
+    let mut chars = name.chars();
+    let mark = chars
+        .next()
+        .with_context(|| format!("'{name}' is not a single mark character"))?;
+    ensure!(chars.next().is_none(), "'{name}' is not a single mark character");
+
+    let (view, doc) = current_ref!(cx.editor);
+    let text = doc.text();
+    let line = text.char_to_line(doc.selection(view.id).primary().cursor(text.slice(..))) + 1;
+
+    MARKS.lock().unwrap().insert((doc.id(), mark), line);
+    Ok(())
+}
+
+/// Resolves and runs a single `:`-command line: a leading range prefix is peeled off first
+/// (see [`parse_range_prefix`]), then a bare `:NUMBER` goes to that line, a name in
+/// `TYPABLE_COMMAND_MAP` runs the matching builtin, and anything else is looked up in the
+/// command-alias registry and then the `:define-command` registry. Shared by the command
+/// prompt and by `run_user_command`, so
+/// user-defined commands can chain builtins (and, within the depth limit, other
+/// user-defined commands).
+fn dispatch_command_line(
+    cx: &mut compositor::Context,
+    input: &str,
+    event: PromptEvent,
+) -> anyhow::Result<()> {
+    let input = input.trim_start();
+
+    // A range prefix doesn't have to be its own shellword: `:%sort` and `:10,20sort` are
+    // peeled off of the raw input before the rest is handed to `Shellwords`.
+    if let Some((start, end, consumed)) = parse_range_prefix(input) {
+        let rest = input[consumed..].trim_start();
+
+        if event == PromptEvent::Validate {
+            let range = resolve_line_range(cx, start, end)?;
+            let range_aware = rest.is_empty()
+                || RANGE_AWARE_COMMANDS.contains(&Shellwords::from(rest).command());
+
+            if range_aware {
+                let (view, doc) = current!(cx.editor);
+                doc.set_selection(view.id, Selection::single(range.anchor, range.head));
+            }
+        }
+
+        return if rest.is_empty() {
+            Ok(())
+        } else {
+            dispatch_command_line(cx, rest, event)
+        };
+    }
+
+    let shellwords = Shellwords::from(input);
+    let command = shellwords.command();
+
+    if command.is_empty() {
+        return Ok(());
+    }
+
+    if command.parse::<usize>().is_ok() {
+        return goto_line_number(cx, Args::from(command), event);
+    }
+
+    if let Some(typable_command) = TYPABLE_COMMAND_MAP.get(command) {
+        let args = Args::from_signature(
+            shellwords.args(),
+            typable_command.signature.parse_mode,
+            typable_command.signature.flags,
+        )?;
+
+        if event == PromptEvent::Validate {
+            typable_command.ensure_signature(args.len())?;
+        }
+
+        return (typable_command.fun)(cx, args, event);
+    }
+
+    if event != PromptEvent::Validate {
+        return Ok(());
+    }
+
+    if COMMAND_ALIASES.lock().unwrap().contains_key(command) {
+        let args = Args::from(shellwords.args());
+        return run_command_alias(cx, command, &args, event);
+    }
+
+    if USER_COMMANDS.lock().unwrap().contains_key(command) {
+        run_user_command(cx, command, event)
+    } else {
+        bail!("no such command: '{command}'")
+    }
+}
+
+/// Splits a `:`-command line into segments at top-level, unquoted `|`/`;` separators, so
+/// `:write | bclose` runs two commands while `:sh grep 'a|b'` keeps its pipe character intact.
+/// A backslash-escaped separator (`\|`) is also kept literal.
+fn split_command_chain(input: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (i, ch) in input.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        match ch {
+            '\\' => escaped = true,
+            '\'' | '"' if quote.is_none() => quote = Some(ch),
+            c if quote == Some(c) => quote = None,
+            '|' | ';' if quote.is_none() => {
+                segments.push(&input[start..i]);
+                start = i + ch.len_utf8();
+            }
+            _ => {}
+        }
+    }
+
+    segments.push(&input[start..]);
+    segments
+}
+
+/// Returns the last segment of a chained command line alongside its byte offset into `input`,
+/// for completion and `doc_fn`. The prompt only exposes the full input text, not the cursor
+/// position, so this assumes left-to-right typing (the cursor trails the last segment) rather
+/// than tracking the cursor exactly.
+fn last_command_chain_segment(input: &str) -> (usize, &str) {
+    let segment = split_command_chain(input).pop().unwrap_or(input);
+    let start = input.len() - segment.len();
+    (start, segment)
+}
+
+fn define_command(
+    cx: &mut compositor::Context,
+    args: Args,
+    event: PromptEvent,
+) -> anyhow::Result<()> {
+    if event != PromptEvent::Validate {
+        return Ok(());
+    }
+
+    if args.has_flag("list") {
+        let mut names: Vec<_> = USER_COMMANDS.lock().unwrap().keys().cloned().collect();
+        names.sort_unstable();
+        cx.editor.set_status(if names.is_empty() {
+            "No user-defined commands".to_string()
+        } else {
+            names.join(", ")
+        });
+        return Ok(());
+    }
+
+    ensure!(
+        args.len() >= 2,
+        "Usage: `:define-command name ':command1' ':command2' ...`"
+    );
+
+    let name = args[0].to_string();
+    ensure!(
+        !TYPABLE_COMMAND_MAP.contains_key(name.as_str()),
+        "`{name}` is already a builtin command"
+    );
+
+    let steps: Vec<String> = args[1..]
+        .iter()
+        .map(|step| step.trim_start_matches(':').to_string())
+        .collect();
+
+    USER_COMMANDS.lock().unwrap().insert(name.clone(), steps);
+    cx.editor.set_status(format!("Defined command `{name}`"));
+    Ok(())
+}
+
+fn undefine_command(
+    cx: &mut compositor::Context,
+    args: Args,
+    event: PromptEvent,
+) -> anyhow::Result<()> {
+    if event != PromptEvent::Validate {
+        return Ok(());
+    }
+
+    ensure!(args.len() == 1, "Usage: `:undefine-command name`");
+
+    let name = &args[0];
+    if USER_COMMANDS.lock().unwrap().remove(name.as_ref()).is_some() {
+        cx.editor.set_status(format!("Removed command `{name}`"));
+        Ok(())
+    } else {
+        bail!("no such user-defined command `{name}`")
+    }
+}
+
 #[allow(clippy::unnecessary_unwrap)]
 pub(super) fn command_mode(cx: &mut Context) {
     let mut prompt = Prompt::new(
         ":".into(),
         Some(':'),
         |editor: &Editor, input: &str| {
-            let shellwords = Shellwords::from(input);
+            // Only the chain segment the cursor is (assumed to be) in takes part in
+            // completion, so completing the second half of `:write | bcl` doesn't also try
+            // to match against `write`.
+            let (seg_start, segment) = last_command_chain_segment(input);
+            let shellwords = Shellwords::from(segment);
             let command = shellwords.command();
             let args = Args::from(shellwords.args());
 
             if command.is_empty() || (args.first().is_none() && !shellwords.ends_with_whitespace())
             {
+                let user_commands = USER_COMMANDS.lock().unwrap();
+                let command_aliases = COMMAND_ALIASES.lock().unwrap();
                 fuzzy_match(
-                    input,
-                    TYPABLE_COMMAND_LIST.iter().map(|command| command.name),
+                    segment,
+                    TYPABLE_COMMAND_LIST
+                        .iter()
+                        .map(|command| command.name)
+                        .chain(user_commands.keys().map(String::as_str))
+                        .chain(command_aliases.keys().map(String::as_str)),
                     false,
                 )
                 .into_iter()
-                .map(|(name, _)| (0.., name.into()))
+                .map(|(name, _)| (seg_start.., name.to_string().into()))
                 .collect()
             } else {
-                // Otherwise, use the command's completer and the last shellword
-                // as completion input.
-                let (word, len) = args.last().map_or(("", 0), |last| (last, last.len()));
-
-                TYPABLE_COMMAND_MAP
-                    .get(command)
-                    .map(|tc| tc.completer_for_argument_number(argument_number_of(&shellwords)))
-                    .map_or_else(Vec::new, |completer| {
-                        completer(editor, word)
-                            .into_iter()
-                            .map(|(range, mut file)| {
-                                file.content = shellwords::escape(file.content);
-
-                                // offset ranges to input
-                                let offset = input.len() - len;
-                                let range = (range.start + offset)..;
-                                (range, file)
-                            })
-                            .collect()
+                // Otherwise, use the command's completer and the last shellword as
+                // completion input. `ParseMode::Literal` commands (the `run-shell-command`/
+                // `pipe` family) parse their entire tail as a single argument so the shell
+                // command line survives without being shellwords-split, but completion still
+                // wants per-word granularity within that tail: the first word names an
+                // executable, everything after it is a filename.
+                let tail = shellwords.args();
+                let (completer, word) = match TYPABLE_COMMAND_MAP.get(command) {
+                    Some(tc) if tc.signature.parse_mode == ParseMode::Literal => {
+                        match tail.rsplit_once(char::is_whitespace) {
+                            Some((_, last_word)) => {
+                                (&tc.signature.completer.var_args, last_word)
+                            }
+                            None => (tc.completer_for_argument_number(0), tail),
+                        }
+                    }
+                    Some(tc) => (
+                        tc.completer_for_argument_number(argument_number_of(&shellwords)),
+                        args.last().unwrap_or(""),
+                    ),
+                    None => return Vec::new(),
+                };
+
+                let len = word.len();
+                completer
+                    .complete(editor, &args, word)
+                    .into_iter()
+                    .map(|(range, mut file)| {
+                        file.content = shellwords::escape(file.content);
+
+                        // offset ranges to input
+                        let offset = input.len() - len;
+                        let range = (range.start + offset)..;
+                        (range, file)
                     })
+                    .collect()
             }
         }, // completion
         move |cx: &mut compositor::Context, input: &str, event: PromptEvent| {
-            let shellwords = Shellwords::from(input);
-            let command = shellwords.command();
-
-            if command.is_empty() {
-                return;
-            }
-
-            // If input is `:NUMBER`, interpret as line number and go there.
-            if command.parse::<usize>().is_ok() {
-                if let Err(err) = typed::goto_line_number(cx, Args::from(command), event) {
-                    cx.editor.set_error(format!("{err}"));
+            for segment in typed::split_command_chain(input) {
+                let segment = segment.trim();
+                if segment.is_empty() {
+                    continue;
                 }
-                return;
-            }
-
-            // Handle typable commands
-            if let Some(command) = typed::TYPABLE_COMMAND_MAP.get(command) {
-                let args = match Args::from_signature(
-                    shellwords.args(),
-                    command.signature.parse_mode,
-                    command.signature.flags,
-                ) {
-                    Ok(args) => args,
-                    Err(err) => {
-                        cx.editor.set_error(err.to_string());
-                        return;
-                    }
-                };
 
-                if event == PromptEvent::Validate {
-                    if let Err(err) = command.ensure_signature(args.len()) {
-                        cx.editor.set_error(err.to_string());
-                        return;
-                    }
-                }
-
-                if let Err(err) = (command.fun)(cx, args, event) {
+                if let Err(err) = typed::dispatch_command_line(cx, segment, event) {
                     cx.editor.set_error(format!("{err}"));
+                    break;
                 }
-            } else if event == PromptEvent::Validate {
-                cx.editor.set_error(format!("no such command: '{command}'"));
             }
         },
     );
 
     prompt.doc_fn = Box::new(|input: &str| {
-        let shellwords = Shellwords::from(input);
+        let (_, segment) = typed::last_command_chain_segment(input);
+        let shellwords = Shellwords::from(segment);
 
         if let Some(command) = typed::TYPABLE_COMMAND_MAP.get(shellwords.command()) {
             return Some(command.prompt().into());
         }
 
+        if let Some(steps) = typed::COMMAND_ALIASES.lock().unwrap().get(shellwords.command()) {
+            return Some(format!("command alias -> {}", steps.join(" | ")).into());
+        }
+
+        if let Some(steps) = typed::USER_COMMANDS.lock().unwrap().get(shellwords.command()) {
+            return Some(format!("user-defined command -> {}", steps.join(" | ")).into());
+        }
+
         None
     });
 
@@ -3964,3 +6255,68 @@ fn test_argument_number_of() {
         assert_eq!(case.1, argument_number_of(&Shellwords::from(case.0)));
     }
 }
+
+#[test]
+fn test_parse_range_prefix() {
+    assert_eq!(
+        parse_range_prefix("10,20"),
+        Some((RangeEndpoint::Absolute(10), RangeEndpoint::Absolute(20), 5))
+    );
+    assert_eq!(
+        parse_range_prefix(".,+5"),
+        Some((RangeEndpoint::Current, RangeEndpoint::Relative(5), 4))
+    );
+    assert_eq!(
+        parse_range_prefix("'a,'b"),
+        Some((RangeEndpoint::Mark('a'), RangeEndpoint::Mark('b'), 5))
+    );
+    assert_eq!(
+        parse_range_prefix("%"),
+        Some((RangeEndpoint::Absolute(1), RangeEndpoint::Last, 1))
+    );
+    assert_eq!(parse_range_prefix("sort"), None);
+    assert_eq!(parse_range_prefix("10"), None);
+
+    // A range doesn't need to be its own shellword: Vim's glued spellings work too, and the
+    // unconsumed remainder (including a following command with no space) is returned as-is.
+    assert_eq!(
+        parse_range_prefix("10,20sort"),
+        Some((RangeEndpoint::Absolute(10), RangeEndpoint::Absolute(20), 5))
+    );
+    assert_eq!(
+        parse_range_prefix("%sort"),
+        Some((RangeEndpoint::Absolute(1), RangeEndpoint::Last, 1))
+    );
+    assert_eq!(
+        parse_range_prefix("%y"),
+        Some((RangeEndpoint::Absolute(1), RangeEndpoint::Last, 1))
+    );
+}
+
+#[test]
+fn test_validate_alias_step() {
+    assert!(validate_alias_step("write").is_ok());
+    assert!(validate_alias_step("goto %arg{0}").is_ok());
+    assert!(validate_alias_step("goto %arg{1..}").is_ok());
+    assert!(validate_alias_step("goto %{line_number}").is_ok());
+    assert!(validate_alias_step("goto %arg{").is_err());
+    assert!(validate_alias_step("no-such-command").is_err());
+}
+
+#[test]
+fn test_split_command_chain() {
+    assert_eq!(split_command_chain("write"), vec!["write"]);
+    assert_eq!(
+        split_command_chain("write | bclose"),
+        vec!["write ", " bclose"]
+    );
+    assert_eq!(
+        split_command_chain("set-option a x ; theme onedark"),
+        vec!["set-option a x ", " theme onedark"]
+    );
+    assert_eq!(
+        split_command_chain("sh grep 'a|b'"),
+        vec!["sh grep 'a|b'"]
+    );
+    assert_eq!(split_command_chain(r"sh echo a\|b"), vec![r"sh echo a\|b"]);
+}